@@ -0,0 +1,98 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index, LitStr};
+
+/// Derives `hallib_rs::record::FixedRecord` for a struct whose fields all implement `hallib_rs::record::FixedField`.
+/// 
+/// The fields are laid out in declaration order with no padding. Numbers are little endian unless
+/// the struct is marked `#[fixed_record(endian = "big")]`.
+#[proc_macro_derive(FixedRecord, attributes(fixed_record))]
+pub fn derive_fixed_record(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut endian = quote!(::hallib_rs::record::Endian::Little);
+    for attr in &input.attrs {
+        if !attr.path().is_ident("fixed_record") {
+            continue
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("endian") {
+                let value: LitStr = meta.value()?.parse()?;
+                endian = match value.value().as_str() {
+                    "little" => quote!(::hallib_rs::record::Endian::Little),
+                    "big" => quote!(::hallib_rs::record::Endian::Big),
+                    _ => return Err(meta.error("endian must be \"little\" or \"big\"")),
+                };
+                Ok(())
+            } else {
+                Err(meta.error("unsupported fixed_record attribute"))
+            }
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(syn::Error::new_spanned(&input, "FixedRecord can only be derived for structs")),
+    };
+
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let accessors: Vec<TokenStream2> = fields.iter().enumerate().map(|(index, field)| match &field.ident {
+        Some(ident) => quote!(#ident),
+        None => {
+            let index = Index::from(index);
+            quote!(#index)
+        },
+    }).collect();
+    let locals: Vec<_> = (0..fields.len()).map(|index| format_ident!("field_{}", index)).collect();
+
+    let construct = match fields {
+        Fields::Named(_) => quote!(Self { #(#accessors: #locals),* }),
+        Fields::Unnamed(_) => quote!(Self ( #(#locals),* )),
+        Fields::Unit => quote!(Self),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::hallib_rs::record::FixedRecord for #name #type_generics #where_clause {
+            const SIZE: usize = 0 #(+ <#types as ::hallib_rs::record::FixedField>::SIZE)*;
+            const ENDIAN: ::hallib_rs::record::Endian = #endian;
+
+            fn encode_into(&self, buffer: &mut [u8]) -> ::core::result::Result<(), ::hallib_rs::Error> {
+                if buffer.len() < <Self as ::hallib_rs::record::FixedRecord>::SIZE {
+                    return ::core::result::Result::Err(::hallib_rs::Error::CapacityExceeded { attempted: <Self as ::hallib_rs::record::FixedRecord>::SIZE, capacity: buffer.len() })
+                }
+                let mut offset = 0;
+                #(
+                    let size = <#types as ::hallib_rs::record::FixedField>::SIZE;
+                    ::hallib_rs::record::FixedField::encode_field(&self.#accessors, &mut buffer[offset..offset + size], #endian);
+                    offset += size;
+                )*
+                let _ = offset;
+                ::core::result::Result::Ok(())
+            }
+
+            fn decode_from(buffer: &[u8]) -> ::core::result::Result<Self, ::hallib_rs::Error> {
+                if buffer.len() < <Self as ::hallib_rs::record::FixedRecord>::SIZE {
+                    return ::core::result::Result::Err(::hallib_rs::Error::MalformedBuffer)
+                }
+                let mut offset = 0;
+                #(
+                    let size = <#types as ::hallib_rs::record::FixedField>::SIZE;
+                    let #locals = <#types as ::hallib_rs::record::FixedField>::decode_field(&buffer[offset..offset + size], #endian)?;
+                    offset += size;
+                )*
+                let _ = offset;
+                ::core::result::Result::Ok(#construct)
+            }
+        }
+    })
+}
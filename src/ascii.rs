@@ -0,0 +1,204 @@
+//! A KeyString that is guaranteed to be ascii.
+//! 
+//! Most keys are ascii identifiers, and knowing that up front makes some things cheaper:
+//! validation is `is_ascii` instead of a utf8 decode, every byte is a char so indexing by char is O(1),
+//! and truncation never has to look for a char boundary.
+
+use crate::{Error, KeyStringN};
+
+/// A KeyString whose contents are all ascii. It has exactly the same layout as `KeyStringN<N>`.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[repr(transparent)]
+pub struct AsciiKey<const N: usize = 64> {
+    key: KeyStringN<N>,
+}
+
+fn check_ascii(bytes: &[u8]) -> Result<(), Error> {
+    // is_ascii checks a word at a time, only look for the position when it fails
+    if bytes.is_ascii() {
+        return Ok(())
+    }
+    let position = bytes.iter().position(|byte| !byte.is_ascii()).unwrap_or(0);
+    Err(Error::NonAscii { position })
+}
+
+impl<const N: usize> AsciiKey<N> {
+
+    pub const CAPACITY: usize = KeyStringN::<N>::CAPACITY;
+
+    /// Fails if s is not ascii or has more than CAPACITY bytes.
+    pub fn try_from_str(s: &str) -> Result<Self, Error> {
+        Self::from_ascii(s.as_bytes())
+    }
+
+    /// Fails if bytes are not ascii or there are more than CAPACITY of them.
+    pub fn from_ascii(bytes: &[u8]) -> Result<Self, Error> {
+        check_ascii(bytes)?;
+        if bytes.len() > Self::CAPACITY {
+            return Err(Error::CapacityExceeded { attempted: bytes.len(), capacity: Self::CAPACITY })
+        }
+        Ok(AsciiKey { key: KeyStringN::from_bytes_unchecked(bytes) })
+    }
+
+    /// Keeps the first CAPACITY bytes and returns how many were cut. Fails if the kept bytes are not ascii.
+    pub fn from_ascii_truncating(bytes: &[u8]) -> Result<(Self, usize), Error> {
+        let min = std::cmp::min(bytes.len(), Self::CAPACITY);
+        Ok((Self::from_ascii(&bytes[0..min])?, bytes.len() - min))
+    }
+
+    pub const fn len(&self) -> usize {
+        self.key.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.key.is_empty()
+    }
+
+    pub const fn as_str(&self) -> &str {
+        self.key.as_str()
+    }
+
+    pub const fn as_bytes(&self) -> &[u8] {
+        self.key.as_bytes()
+    }
+
+    pub const fn as_keystring(&self) -> &KeyStringN<N> {
+        &self.key
+    }
+
+    /// The char at index, in O(1) since every char is one byte.
+    pub fn char_at(&self, index: usize) -> Option<char> {
+        self.as_bytes().get(index).map(|byte| *byte as char)
+    }
+
+    /// The chars in range, in O(1). Returns None if the range is out of bounds.
+    pub fn substring(&self, range: std::ops::Range<usize>) -> Option<&str> {
+        // Every index is a char boundary
+        self.as_str().get(range)
+    }
+
+    /// Appends s. Fails without changing anything if it is not ascii or does not fit.
+    pub fn push(&mut self, s: &str) -> Result<(), Error> {
+        check_ascii(s.as_bytes())?;
+        self.key.push(s)
+    }
+
+    pub fn make_ascii_lowercase(&mut self) {
+        self.key.make_ascii_lowercase()
+    }
+
+    pub fn make_ascii_uppercase(&mut self) {
+        self.key.make_ascii_uppercase()
+    }
+
+    pub fn eq_ignore_ascii_case(&self, other: &Self) -> bool {
+        self.key.eq_ignore_ascii_case(&other.key)
+    }
+
+    pub fn cmp_ignore_ascii_case(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_bytes().iter().map(u8::to_ascii_lowercase).cmp(other.as_bytes().iter().map(u8::to_ascii_lowercase))
+    }
+
+}
+
+impl<const N: usize> KeyStringN<N> {
+
+    pub fn is_ascii(&self) -> bool {
+        self.as_bytes().is_ascii()
+    }
+
+}
+
+impl<const N: usize> std::ops::Deref for AsciiKey<N> {
+    type Target = KeyStringN<N>;
+
+    fn deref(&self) -> &KeyStringN<N> {
+        &self.key
+    }
+}
+
+impl<const N: usize> AsRef<str> for AsciiKey<N> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> std::fmt::Debug for AsciiKey<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsciiKey").field("inner", &self.as_str()).finish()
+    }
+}
+
+impl<const N: usize> std::fmt::Display for AsciiKey<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for AsciiKey<N> {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::try_from_str(s)
+    }
+}
+
+impl<const N: usize> TryFrom<&[u8]> for AsciiKey<N> {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_ascii(bytes)
+    }
+}
+
+impl<const N: usize> std::str::FromStr for AsciiKey<N> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from_str(s)
+    }
+}
+
+/// Fails if the KeyString is not ascii.
+impl<const N: usize> TryFrom<KeyStringN<N>> for AsciiKey<N> {
+    type Error = Error;
+
+    fn try_from(key: KeyStringN<N>) -> Result<Self, Self::Error> {
+        check_ascii(key.as_bytes())?;
+        Ok(AsciiKey { key })
+    }
+}
+
+impl<const N: usize> From<AsciiKey<N>> for KeyStringN<N> {
+    fn from(key: AsciiKey<N>) -> Self {
+        key.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_keys() {
+        let mut key = AsciiKey::<16>::try_from_str("users").unwrap();
+        assert_eq!(key.char_at(1), Some('s'));
+        assert_eq!(key.char_at(5), None);
+        assert_eq!(key.substring(1..3), Some("se"));
+        key.push("_ID").unwrap();
+        assert_eq!(key.push("é"), Err(Error::NonAscii { position: 0 }));
+        assert_eq!(key.as_str(), "users_ID");
+        assert!(key.eq_ignore_ascii_case(&AsciiKey::try_from_str("USERS_id").unwrap()));
+
+        assert_eq!(AsciiKey::<16>::try_from_str("caf\u{e9}"), Err(Error::NonAscii { position: 3 }));
+        assert!(AsciiKey::<4>::try_from_str("users").is_err());
+        let (cut, dropped) = AsciiKey::<4>::from_ascii_truncating(b"users").unwrap();
+        assert_eq!((cut.as_str(), dropped), ("use", 2));
+
+        let as_keystring: KeyStringN<16> = key.into();
+        assert_eq!(AsciiKey::try_from(as_keystring).unwrap(), key);
+        assert!(AsciiKey::try_from(KeyStringN::<16>::from("ø")).is_err());
+        assert!(key.is_ascii());
+        assert!(key < AsciiKey::try_from_str("users_b").unwrap());
+    }
+}
@@ -0,0 +1,162 @@
+//! Case insensitive comparison of KeyStrings.
+//! 
+//! `CaseInsensitive` folds ascii letters only, which is cheap and what identifiers usually need.
+//! `UnicodeCaseInsensitive` folds every char that has a single char lowercase mapping, and final sigma to sigma,
+//! which is close to Unicode simple case folding without needing the full tables.
+//! In both, Eq, Hash and Ord agree with each other, so the wrappers work as HashMap and BTreeMap keys.
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use crate::{KeyString, KeyStringN};
+
+impl<const N: usize> KeyStringN<N> {
+
+    /// Converts ascii letters to lowercase in place. Other chars are left as they are.
+    pub fn make_ascii_lowercase(&mut self) {
+        let len = self.len();
+        self.inner[0..len].make_ascii_lowercase();
+    }
+
+    /// Converts ascii letters to uppercase in place. Other chars are left as they are.
+    pub fn make_ascii_uppercase(&mut self) {
+        let len = self.len();
+        self.inner[0..len].make_ascii_uppercase();
+    }
+
+    pub fn eq_ignore_ascii_case(&self, other: &Self) -> bool {
+        self.as_bytes().eq_ignore_ascii_case(other.as_bytes())
+    }
+
+    /// Compares like `Ord` would if both were lowercased first.
+    pub fn cmp_ignore_ascii_case(&self, other: &Self) -> Ordering {
+        ascii_cmp(self.as_str(), other.as_str())
+    }
+
+}
+
+fn ascii_cmp(a: &str, b: &str) -> Ordering {
+    let a = a.bytes().map(|byte| byte.to_ascii_lowercase());
+    let b = b.bytes().map(|byte| byte.to_ascii_lowercase());
+    a.cmp(b)
+}
+
+fn fold_char(c: char) -> char {
+    if c == 'ς' {
+        return 'σ'
+    }
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(folded), None) => folded,
+        _ => c,
+    }
+}
+
+fn unicode_folded(s: &str) -> impl Iterator<Item = char> + '_ {
+    s.chars().map(fold_char)
+}
+
+/// Wraps a string so that Eq, Hash and Ord ignore ascii case.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseInsensitive<T = KeyString>(pub T);
+
+impl<T: AsRef<str>> PartialEq for CaseInsensitive<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_ref().eq_ignore_ascii_case(other.0.as_ref())
+    }
+}
+
+impl<T: AsRef<str>> Eq for CaseInsensitive<T> {}
+
+impl<T: AsRef<str>> Hash for CaseInsensitive<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for byte in self.0.as_ref().bytes() {
+            state.write_u8(byte.to_ascii_lowercase());
+        }
+        // Same terminator as str uses, so ("ab", "c") and ("a", "bc") hash differently in a tuple
+        state.write_u8(0xff);
+    }
+}
+
+impl<T: AsRef<str>> Ord for CaseInsensitive<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        ascii_cmp(self.0.as_ref(), other.0.as_ref())
+    }
+}
+
+impl<T: AsRef<str>> PartialOrd for CaseInsensitive<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A KeyString whose Eq, Hash and Ord ignore ascii case.
+pub type IKeyString<const N: usize = 64> = CaseInsensitive<KeyStringN<N>>;
+
+/// Wraps a string so that Eq, Hash and Ord ignore case for all of Unicode, see the module documentation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnicodeCaseInsensitive<T = KeyString>(pub T);
+
+impl<T: AsRef<str>> PartialEq for UnicodeCaseInsensitive<T> {
+    fn eq(&self, other: &Self) -> bool {
+        unicode_folded(self.0.as_ref()).eq(unicode_folded(other.0.as_ref()))
+    }
+}
+
+impl<T: AsRef<str>> Eq for UnicodeCaseInsensitive<T> {}
+
+impl<T: AsRef<str>> Hash for UnicodeCaseInsensitive<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for c in unicode_folded(self.0.as_ref()) {
+            state.write_u32(c as u32);
+        }
+        state.write_u8(0xff);
+    }
+}
+
+impl<T: AsRef<str>> Ord for UnicodeCaseInsensitive<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        unicode_folded(self.0.as_ref()).cmp(unicode_folded(other.0.as_ref()))
+    }
+}
+
+impl<T: AsRef<str>> PartialOrd for UnicodeCaseInsensitive<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeSet, HashSet};
+
+    #[test]
+    fn ascii_case() {
+        let mut key = KeyStringN::<64>::from("Users_Ærø");
+        key.make_ascii_lowercase();
+        assert_eq!(key.as_str(), "users_Ærø");
+        key.make_ascii_uppercase();
+        assert_eq!(key.as_str(), "USERS_ÆRø");
+        assert!(key.eq_ignore_ascii_case(&KeyString::from("users_ÆRø")));
+        assert_eq!(KeyStringN::<64>::from("a_B").cmp_ignore_ascii_case(&KeyString::from("A_a")), Ordering::Greater);
+
+        let names: HashSet<IKeyString> = ["Users", "USERS", "orders"].iter().map(|s| CaseInsensitive(KeyString::from(*s))).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&CaseInsensitive(KeyString::from("uSeRs"))));
+
+        let sorted: BTreeSet<CaseInsensitive<&str>> = ["b", "A", "a", "C"].into_iter().map(CaseInsensitive).collect();
+        assert_eq!(sorted.iter().map(|c| c.0.to_ascii_lowercase()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert_ne!(CaseInsensitive("Æ"), CaseInsensitive("æ"));
+    }
+
+    #[test]
+    fn unicode_case() {
+        assert_eq!(UnicodeCaseInsensitive("ÆRØ"), UnicodeCaseInsensitive("ærø"));
+        assert_eq!(UnicodeCaseInsensitive("ΟΔΟΣ"), UnicodeCaseInsensitive("οδος"));
+        assert_ne!(UnicodeCaseInsensitive("a"), UnicodeCaseInsensitive("b"));
+
+        let names: HashSet<UnicodeCaseInsensitive> = ["Ærø", "ÆRØ", "ærø"].iter().map(|s| UnicodeCaseInsensitive(KeyString::from(*s))).collect();
+        assert_eq!(names.len(), 1);
+    }
+}
@@ -0,0 +1,78 @@
+//! Decoding of fixed width fields, where a string is padded out to the size of the field.
+//! 
+//! Every function takes the whole field. An empty or all-padding field decodes to "",
+//! and only a field with invalid contents is an error.
+
+use std::ops::Range;
+
+use crate::Error;
+
+/// The range of the field before its trailing 0 bytes. Leading and interior 0 bytes are part of the content.
+pub fn nul_padded_span(bytes: &[u8]) -> Range<usize> {
+    let end = bytes.iter().rposition(|byte| *byte != 0).map_or(0, |index| index + 1);
+    0..end
+}
+
+/// Strips the trailing 0 bytes and checks that the rest is utf8. Leading and interior 0 bytes are kept.
+pub fn nul_padded(bytes: &[u8]) -> Result<&str, Error> {
+    Ok(std::str::from_utf8(&bytes[nul_padded_span(bytes)])?)
+}
+
+/// Like `nul_padded`, but fails with Error::InteriorNul if there is a 0 byte before the padding.
+pub fn nul_padded_strict(bytes: &[u8]) -> Result<&str, Error> {
+    let span = nul_padded_span(bytes);
+    if let Some(position) = bytes[span.clone()].iter().position(|byte| *byte == 0) {
+        return Err(Error::InteriorNul { position })
+    }
+    Ok(std::str::from_utf8(&bytes[span])?)
+}
+
+/// The range of the field before its trailing spaces. Leading and interior spaces are part of the content.
+pub fn space_padded_span(bytes: &[u8]) -> Range<usize> {
+    let end = bytes.iter().rposition(|byte| *byte != b' ').map_or(0, |index| index + 1);
+    0..end
+}
+
+/// Strips the trailing spaces and checks that the rest is utf8. Leading and interior spaces are kept.
+pub fn space_padded(bytes: &[u8]) -> Result<&str, Error> {
+    Ok(std::str::from_utf8(&bytes[space_padded_span(bytes)])?)
+}
+
+/// Like `space_padded`, but fails with Error::InteriorNul if the field contains a 0 byte,
+/// which usually means a nul padded field was read as a space padded one.
+pub fn space_padded_strict(bytes: &[u8]) -> Result<&str, Error> {
+    let span = space_padded_span(bytes);
+    if let Some(position) = bytes[span.clone()].iter().position(|byte| *byte == 0) {
+        return Err(Error::InteriorNul { position })
+    }
+    Ok(std::str::from_utf8(&bytes[span])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nul_padding() {
+        assert_eq!(nul_padded(b""), Ok(""));
+        assert_eq!(nul_padded(b"\0\0\0"), Ok(""));
+        assert_eq!(nul_padded(b"abc\0\0"), Ok("abc"));
+        assert_eq!(nul_padded(b"\0\0a"), Ok("\0\0a"));
+        assert_eq!(nul_padded(b"a\0b\0"), Ok("a\0b"));
+        assert_eq!(nul_padded_span(b"a\0b\0"), 0..3);
+        assert!(matches!(nul_padded(b"\xff\0"), Err(Error::InvalidUtf8(_))));
+
+        assert_eq!(nul_padded_strict(b"abc\0\0"), Ok("abc"));
+        assert_eq!(nul_padded_strict(b"a\0b\0"), Err(Error::InteriorNul { position: 1 }));
+        assert_eq!(nul_padded_strict(b"\0a"), Err(Error::InteriorNul { position: 0 }));
+    }
+
+    #[test]
+    fn space_padding() {
+        assert_eq!(space_padded(b"    "), Ok(""));
+        assert_eq!(space_padded(b"New York  "), Ok("New York"));
+        assert_eq!(space_padded(b"  x "), Ok("  x"));
+        assert_eq!(space_padded_span(b"ab  "), 0..2);
+        assert_eq!(space_padded_strict(b"ab\0\0"), Err(Error::InteriorNul { position: 2 }));
+    }
+}
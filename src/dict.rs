@@ -0,0 +1,296 @@
+//! Dictionary encoding for columns of KeyStrings with few distinct values.
+//! 
+//! The distinct keys are kept once in a sorted dictionary and every row is a code into it, packed into as few
+//! bits as the dictionary size needs. Because the dictionary is sorted, codes compare like the keys they stand
+//! for, so equality and range predicates are answered by comparing integers without touching the keys.
+//! 
+//! The serialized format is a little endian header of four u32s (rows, N, dictionary size, bit width), then the
+//! dictionary in the `raw()` layout, then the packed codes as little endian u64s.
+
+use crate::{Error, KeyStringN};
+use std::ops::{Bound, RangeBounds};
+
+const HEADER: usize = 16;
+
+/// A column of `KeyStringN<N>` stored as a sorted dictionary plus bit-packed codes. See the module documentation.
+/// It holds at most `u32::MAX` rows, since the serialized format stores counts as u32.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictColumn<const N: usize = 64> {
+    dictionary: Vec<KeyStringN<N>>,
+    words: Vec<u64>,
+    bit_width: u32,
+    len: usize,
+}
+
+fn bit_width_for(dictionary_len: usize) -> u32 {
+    (usize::BITS - dictionary_len.saturating_sub(1).leading_zeros()).max(1)
+}
+
+impl<const N: usize> DictColumn<N> {
+
+    pub fn new() -> Self {
+        DictColumn { dictionary: Vec::new(), words: Vec::new(), bit_width: 1, len: 0 }
+    }
+
+    pub fn from_column(column: &[KeyStringN<N>]) -> Self {
+        let mut result = Self::new();
+        result.extend(column.iter().copied());
+        result
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The distinct keys, sorted. A row's code is its key's index here.
+    pub fn dictionary(&self) -> &[KeyStringN<N>] {
+        &self.dictionary
+    }
+
+    /// How many bits each code takes.
+    pub fn bit_width(&self) -> u32 {
+        self.bit_width
+    }
+
+    pub fn code(&self, index: usize) -> Option<u32> {
+        (index < self.len).then(|| self.code_unchecked(index))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&KeyStringN<N>> {
+        self.code(index).map(|code| &self.dictionary[code as usize])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &KeyStringN<N>> + '_ {
+        (0..self.len).map(|index| &self.dictionary[self.code_unchecked(index) as usize])
+    }
+
+    pub fn to_vec(&self) -> Vec<KeyStringN<N>> {
+        self.iter().copied().collect()
+    }
+
+    fn code_unchecked(&self, index: usize) -> u32 {
+        let bit = index * self.bit_width as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mut value = self.words[word] >> offset;
+        if offset + self.bit_width as usize > 64 {
+            value |= self.words[word + 1] << (64 - offset);
+        }
+        (value & ((1 << self.bit_width) - 1)) as u32
+    }
+
+    fn push_code(&mut self, code: u32) {
+        let bit = self.len * self.bit_width as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        if offset == 0 {
+            self.words.push(0);
+        }
+        self.words[word] |= (code as u64) << offset;
+        if offset + self.bit_width as usize > 64 {
+            self.words.push((code as u64) >> (64 - offset));
+        }
+        self.len += 1;
+    }
+
+    /// Appends a row. A key that is not in the dictionary yet re-encodes every row, since codes after it shift
+    /// and the bit width may grow; use `extend` to add many new keys with a single re-encoding.
+    /// Panics if the column would have more than `u32::MAX` rows.
+    pub fn push(&mut self, key: KeyStringN<N>) {
+        self.extend(std::iter::once(key));
+    }
+
+    /// Rebuilds the codes for a new dictionary, which must contain every key of the current one.
+    fn reencode(&mut self, dictionary: Vec<KeyStringN<N>>) {
+        let remap: Vec<u32> = self.dictionary.iter().map(|key| dictionary.binary_search(key).unwrap() as u32).collect();
+        let codes: Vec<u32> = (0..self.len).map(|index| remap[self.code_unchecked(index) as usize]).collect();
+        self.bit_width = bit_width_for(dictionary.len());
+        self.dictionary = dictionary;
+        self.words.clear();
+        self.len = 0;
+        for code in codes {
+            self.push_code(code);
+        }
+    }
+
+    /// The rows whose key equals key.
+    pub fn select_eq(&self, key: &KeyStringN<N>) -> Vec<usize> {
+        match self.dictionary.binary_search(key) {
+            Ok(code) => self.select_codes(code as u32, code as u32 + 1),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// The rows whose key is in range.
+    pub fn select_range<R: RangeBounds<KeyStringN<N>>>(&self, range: R) -> Vec<usize> {
+        let start = match range.start_bound() {
+            Bound::Included(key) => self.dictionary.partition_point(|k| k < key),
+            Bound::Excluded(key) => self.dictionary.partition_point(|k| k <= key),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => self.dictionary.partition_point(|k| k <= key),
+            Bound::Excluded(key) => self.dictionary.partition_point(|k| k < key),
+            Bound::Unbounded => self.dictionary.len(),
+        };
+        if start >= end {
+            return Vec::new()
+        }
+        self.select_codes(start as u32, end as u32)
+    }
+
+    fn select_codes(&self, start: u32, end: u32) -> Vec<usize> {
+        (0..self.len).filter(|index| (start..end).contains(&self.code_unchecked(*index))).collect()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(HEADER + self.dictionary.len() * N + self.words.len() * 8);
+        output.extend_from_slice(&(self.len as u32).to_le_bytes());
+        output.extend_from_slice(&(N as u32).to_le_bytes());
+        output.extend_from_slice(&(self.dictionary.len() as u32).to_le_bytes());
+        output.extend_from_slice(&self.bit_width.to_le_bytes());
+        output.extend_from_slice(KeyStringN::slice_as_bytes(&self.dictionary));
+        for word in &self.words {
+            output.extend_from_slice(&word.to_le_bytes());
+        }
+        output
+    }
+
+    /// Reads a column written by `to_bytes`.
+    /// Fails if the header does not match the rest, the size is not N, the dictionary is invalid or not sorted
+    /// and unique, or a code is outside the dictionary.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < HEADER {
+            return Err(Error::MalformedBuffer)
+        }
+        let field = |i: usize| u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()) as usize;
+        let (len, size, dictionary_len, bit_width) = (field(0), field(1), field(2), field(3));
+        if size != N || bit_width as u32 != bit_width_for(dictionary_len) {
+            return Err(Error::MalformedBuffer)
+        }
+        let dictionary_end = dictionary_len.checked_mul(N).and_then(|n| n.checked_add(HEADER)).ok_or(Error::MalformedBuffer)?;
+        let word_count = len.checked_mul(bit_width).ok_or(Error::MalformedBuffer)?.div_ceil(64);
+        if word_count.checked_mul(8).and_then(|n| n.checked_add(dictionary_end)) != Some(bytes.len()) {
+            return Err(Error::MalformedBuffer)
+        }
+        let dictionary = KeyStringN::<N>::slice_from_bytes(&bytes[HEADER..dictionary_end])?.to_vec();
+        if !dictionary.windows(2).all(|pair| pair[0] < pair[1]) {
+            return Err(Error::MalformedBuffer)
+        }
+        let words: Vec<u64> = bytes[dictionary_end..].chunks_exact(8).map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap())).collect();
+        // Bits after the last code must be 0, since push_code ORs the next code into them
+        let used = (len * bit_width) % 64;
+        if used != 0 && words.last().is_some_and(|word| word >> used != 0) {
+            return Err(Error::MalformedBuffer)
+        }
+        let column = DictColumn { dictionary, words, bit_width: bit_width as u32, len };
+        if (0..len).any(|index| column.code_unchecked(index) as usize >= column.dictionary.len()) {
+            return Err(Error::MalformedBuffer)
+        }
+        Ok(column)
+    }
+
+}
+
+impl<const N: usize> Default for DictColumn<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Extend<KeyStringN<N>> for DictColumn<N> {
+    /// Re-encodes the existing rows at most once, however many new keys there are.
+    /// Panics if the column would have more than `u32::MAX` rows.
+    fn extend<I: IntoIterator<Item = KeyStringN<N>>>(&mut self, iter: I) {
+        let keys: Vec<KeyStringN<N>> = iter.into_iter().collect();
+        assert!(self.len + keys.len() <= u32::MAX as usize, "a DictColumn cannot have more than u32::MAX rows");
+        let mut new_keys: Vec<KeyStringN<N>> = keys.iter().filter(|key| self.dictionary.binary_search(key).is_err()).copied().collect();
+        if !new_keys.is_empty() {
+            new_keys.extend_from_slice(&self.dictionary);
+            new_keys.sort();
+            new_keys.dedup();
+            self.reencode(new_keys);
+        }
+        for key in keys {
+            let code = self.dictionary.binary_search(&key).unwrap();
+            self.push_code(code as u32);
+        }
+    }
+}
+
+impl<const N: usize> FromIterator<KeyStringN<N>> for DictColumn<N> {
+    fn from_iter<I: IntoIterator<Item = KeyStringN<N>>>(iter: I) -> Self {
+        let mut column = Self::new();
+        column.extend(iter);
+        column
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    #[test]
+    fn encoding_and_predicates() {
+        let statuses = ["open", "closed", "pending", "open", "open", "closed"];
+        let rows: Vec<KeyString> = statuses.iter().map(|s| KeyString::from(*s)).collect();
+        let mut column = DictColumn::from_column(&rows);
+        assert_eq!(column.len(), 6);
+        assert_eq!(column.bit_width(), 2);
+        assert_eq!(column.dictionary().len(), 3);
+        assert_eq!(column.to_vec(), rows);
+        assert_eq!(column.get(2).unwrap().as_str(), "pending");
+        assert_eq!(column.get(6), None);
+
+        assert_eq!(column.select_eq(&KeyString::from("open")), vec![0, 3, 4]);
+        assert!(column.select_eq(&KeyString::from("missing")).is_empty());
+        assert_eq!(column.select_range(KeyString::from("o")..), vec![0, 2, 3, 4]);
+        assert_eq!(column.select_range(..=KeyString::from("open")), vec![0, 1, 3, 4, 5]);
+        assert!(column.select_range(KeyString::from("q")..KeyString::from("a")).is_empty());
+
+        // New keys shift codes and widen them; old rows must still read back the same
+        let mut expected = rows.clone();
+        for i in 0..40 {
+            let key = KeyString::from(format!("status_{:02}", i * 7 % 40).as_str());
+            column.push(key);
+            expected.push(key);
+        }
+        column.extend(rows.iter().copied());
+        expected.extend(rows.iter().copied());
+        assert_eq!(column.bit_width(), 6);
+        assert_eq!(column.to_vec(), expected);
+        assert_eq!(column.select_eq(&KeyString::from("closed")), vec![1, 5, 47, 51]);
+    }
+
+    #[test]
+    fn serialization() {
+        let column: DictColumn = (0..200).map(|i| KeyString::from(["DE", "FR", "US", "JP", "BR"][i % 5])).collect();
+        let bytes = column.to_bytes();
+        assert_eq!(bytes.len(), 16 + 5 * 64 + (200 * 3usize).div_ceil(64) * 8);
+        assert_eq!(DictColumn::from_bytes(&bytes).unwrap(), column);
+        assert!(DictColumn::<32>::from_bytes(&bytes).is_err());
+        assert!(DictColumn::<64>::from_bytes(&bytes[0..bytes.len() - 1]).is_err());
+
+        // A code of 7 points past the five keys in the dictionary
+        let mut bad_code = bytes.clone();
+        bad_code[16 + 5 * 64] |= 0b111;
+        assert!(DictColumn::<64>::from_bytes(&bad_code).is_err());
+        // A stray bit after the last code would turn into part of the next pushed code
+        let mut stray = bytes.clone();
+        let last_word = stray.len() - 8;
+        stray[last_word + 3] |= 0x40;
+        assert!(DictColumn::<64>::from_bytes(&stray).is_err());
+        let mut small: DictColumn = ["a", "b"].map(KeyString::from).into_iter().collect();
+        let mut stray = small.to_bytes();
+        let last_word = stray.len() - 8;
+        stray[last_word] |= 0b100;
+        assert_eq!(DictColumn::<64>::from_bytes(&stray), Err(Error::MalformedBuffer));
+        small = DictColumn::from_bytes(&small.to_bytes()).unwrap();
+        small.push(KeyString::from("a"));
+        assert_eq!(small.to_vec(), ["a", "b", "a"].map(KeyString::from));
+        assert_eq!(DictColumn::<64>::from_bytes(&DictColumn::<64>::new().to_bytes()).unwrap(), DictColumn::new());
+    }
+}
@@ -0,0 +1,73 @@
+use std::{num::{ParseFloatError, ParseIntError}, str::{ParseBoolError, Utf8Error}};
+
+/// The errors returned by the fallible functions in this crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The bytes were not valid utf8.
+    InvalidUtf8(Utf8Error),
+    /// The result would have needed `attempted` bytes but only `capacity` fit.
+    CapacityExceeded { attempted: usize, capacity: usize },
+    /// A 0 byte was found at `position` where only padding or the end of the string was allowed.
+    InteriorNul { position: usize },
+    /// A byte at `position` was not ascii where only ascii was allowed.
+    NonAscii { position: usize },
+    /// A buffer did not have the layout it was supposed to, e.g. the wrong size or non-zero padding.
+    MalformedBuffer,
+    /// The contents could not be parsed as an integer.
+    ParseInt(ParseIntError),
+    /// The contents could not be parsed as a float.
+    ParseFloat(ParseFloatError),
+    /// The contents could not be parsed as a bool.
+    ParseBool(ParseBoolError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidUtf8(e) => write!(f, "invalid utf8: {}", e),
+            Error::CapacityExceeded { attempted, capacity } => write!(f, "capacity exceeded: {} bytes do not fit in a capacity of {}", attempted, capacity),
+            Error::InteriorNul { position } => write!(f, "interior nul byte at position {}", position),
+            Error::NonAscii { position } => write!(f, "non-ascii byte at position {}", position),
+            Error::MalformedBuffer => write!(f, "malformed buffer"),
+            Error::ParseInt(e) => write!(f, "could not parse integer: {}", e),
+            Error::ParseFloat(e) => write!(f, "could not parse float: {}", e),
+            Error::ParseBool(e) => write!(f, "could not parse bool: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidUtf8(e) => Some(e),
+            Error::ParseInt(e) => Some(e),
+            Error::ParseFloat(e) => Some(e),
+            Error::ParseBool(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(e: Utf8Error) -> Self {
+        Error::InvalidUtf8(e)
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(e: ParseIntError) -> Self {
+        Error::ParseInt(e)
+    }
+}
+
+impl From<ParseFloatError> for Error {
+    fn from(e: ParseFloatError) -> Self {
+        Error::ParseFloat(e)
+    }
+}
+
+impl From<ParseBoolError> for Error {
+    fn from(e: ParseBoolError) -> Self {
+        Error::ParseBool(e)
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::Write;
+
+use crate::{Error, KeyStringN};
+
+/// Appends formatted text to the KeyString without allocating, so `write!(key, "{}_{}", table, id)` works.
+/// Returns fmt::Error if a piece does not fit. The pieces written before it are kept.
+impl<const N: usize> Write for KeyStringN<N> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.push(s).map_err(|_| std::fmt::Error)
+    }
+}
+
+/// Counts the bytes of formatted text, so a capacity error can report how long it would have been.
+struct LengthCounter(usize);
+
+impl Write for LengthCounter {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+impl<const N: usize> KeyStringN<N> {
+
+    /// Builds a KeyString from format_args! without allocating. Fails if the text does not fit.
+    /// The `keystring_format!` macro is the usual way to call this.
+    pub fn from_fmt(args: std::fmt::Arguments) -> Result<Self, Error> {
+        let mut output = Self::empty();
+        if output.write_fmt(args).is_err() {
+            let mut counter = LengthCounter(0);
+            let _ = counter.write_fmt(args);
+            return Err(Error::CapacityExceeded { attempted: counter.0, capacity: Self::CAPACITY })
+        }
+        Ok(output)
+    }
+
+}
+
+/// Like `format!`, but builds a KeyString on the stack and returns `Result<KeyString, Error>`.
+/// 
+/// `keystring_format!("{}_{}", table, id)` makes a `KeyString` with the default size,
+/// `keystring_format!(16; "{}_{}", table, id)` makes a `KeyStringN<16>`.
+#[macro_export]
+macro_rules! keystring_format {
+    ($n:expr; $($arg:tt)*) => {
+        $crate::KeyStringN::<$n>::from_fmt(format_args!($($arg)*))
+    };
+    ($($arg:tt)*) => {
+        $crate::KeyString::from_fmt(format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_format() {
+        let (id, suffix) = (42, "x");
+        let mut key = KeyStringN::<16>::from("users_");
+        write!(key, "{}_{}", id, suffix).unwrap();
+        assert_eq!(key.as_str(), "users_42_x");
+        assert!(write!(key, "{}", suffix.repeat(8)).is_err());
+
+        let table = "orders";
+        let key = keystring_format!("{}/{}", table, 7).unwrap();
+        assert_eq!(key.as_str(), "orders/7");
+        assert_eq!(keystring_format!("plain").unwrap().as_str(), "plain");
+        assert_eq!(keystring_format!(8; "{}/{}", table, 7), Err(Error::CapacityExceeded { attempted: 8, capacity: 7 }));
+    }
+}
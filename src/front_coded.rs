@@ -0,0 +1,306 @@
+//! Front coding for sorted columns of KeyStrings.
+//! 
+//! Sorted keys tend to share long prefixes with the key before them, so each key is stored as the number of bytes
+//! it shares with the previous key followed by the rest. Every `block_size` keys a restart stores a key whole,
+//! so random access and binary search only decode one block.
+//! 
+//! The serialized format is a little endian header of four u32s (count, N, block size, data length) followed by
+//! the entries, each of which is a u8 shared length, a u8 suffix length and the suffix bytes.
+
+use crate::{Error, KeyStringN};
+use std::cmp::Ordering;
+
+const HEADER: usize = 16;
+
+/// A sorted column of `KeyStringN<N>`, prefix compressed in restart blocks. See the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontCodedKeys<const N: usize = 64> {
+    data: Vec<u8>,
+    /// Where each block starts in data
+    restarts: Vec<u32>,
+    len: usize,
+    block_size: usize,
+}
+
+/// Decodes one entry at position, on top of the previous key in buffer. Returns the position of the next entry.
+fn decode_entry(data: &[u8], position: usize, buffer: &mut [u8; 256], length: &mut usize) -> usize {
+    let shared = data[position] as usize;
+    let suffix = data[position + 1] as usize;
+    buffer[shared..shared + suffix].copy_from_slice(&data[position + 2..position + 2 + suffix]);
+    *length = shared + suffix;
+    position + 2 + suffix
+}
+
+impl<const N: usize> FrontCodedKeys<N> {
+
+    pub const DEFAULT_BLOCK_SIZE: usize = 16;
+
+    /// Panics if the keys are not sorted.
+    pub fn from_sorted(keys: &[KeyStringN<N>]) -> Self {
+        Self::with_block_size(keys, Self::DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Larger blocks compress better but make random access decode more keys.
+    /// Panics if the keys are not sorted, block_size is 0 or does not fit in a u32,
+    /// or the encoded keys take more than 4 GiB, since the serialized format stores sizes as u32.
+    pub fn with_block_size(keys: &[KeyStringN<N>], block_size: usize) -> Self {
+        assert!(block_size > 0 && block_size <= u32::MAX as usize, "block size must be between 1 and u32::MAX");
+        assert!(keys.is_sorted(), "keys must be sorted");
+        let mut data = Vec::new();
+        let mut restarts = Vec::with_capacity(keys.len().div_ceil(block_size));
+        let mut previous: &[u8] = &[];
+        for (index, key) in keys.iter().enumerate() {
+            let bytes = key.as_bytes();
+            let shared = if index.is_multiple_of(block_size) {
+                restarts.push(data.len() as u32);
+                0
+            } else {
+                previous.iter().zip(bytes).take_while(|(a, b)| a == b).count()
+            };
+            data.push(shared as u8);
+            data.push((bytes.len() - shared) as u8);
+            data.extend_from_slice(&bytes[shared..]);
+            previous = bytes;
+        }
+        assert!(data.len() <= u32::MAX as usize, "front coded keys cannot take more than 4 GiB");
+        FrontCodedKeys { data, restarts, len: keys.len(), block_size }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// The number of bytes the encoded keys take, without the header.
+    pub fn encoded_len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<KeyStringN<N>> {
+        if index >= self.len {
+            return None
+        }
+        let mut buffer = [0; 256];
+        let mut length = 0;
+        let mut position = self.restarts[index / self.block_size] as usize;
+        for _ in 0..=index % self.block_size {
+            position = decode_entry(&self.data, position, &mut buffer, &mut length);
+        }
+        // Safe since the entries were checked when building or reading them
+        Some(KeyStringN::from_bytes_unchecked(&buffer[0..length]))
+    }
+
+    /// Like `slice::binary_search`: Ok with the index of a matching key, or Err with where it would be inserted.
+    pub fn binary_search(&self, key: &KeyStringN<N>) -> Result<usize, usize> {
+        let target = key.as_bytes();
+        // Block starts are stored whole, so they can be compared without decoding anything else
+        let restart_key = |block: usize| {
+            let position = self.restarts[block] as usize;
+            &self.data[position + 2..position + 2 + self.data[position + 1] as usize]
+        };
+        let (mut low, mut high) = (0, self.restarts.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            if restart_key(middle) <= target {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        let block = low;
+        if block == 0 {
+            return Err(0)
+        }
+        let block = block - 1;
+        let mut buffer = [0; 256];
+        let mut length = 0;
+        let mut position = self.restarts[block] as usize;
+        let first = block * self.block_size;
+        for index in first..(first + self.block_size).min(self.len) {
+            position = decode_entry(&self.data, position, &mut buffer, &mut length);
+            match buffer[0..length].cmp(target) {
+                Ordering::Less => (),
+                Ordering::Equal => return Ok(index),
+                Ordering::Greater => return Err(index),
+            }
+        }
+        Err((first + self.block_size).min(self.len))
+    }
+
+    pub fn iter(&self) -> Iter<'_, N> {
+        Iter { data: &self.data, position: 0, buffer: [0; 256], length: 0 }
+    }
+
+    pub fn to_vec(&self) -> Vec<KeyStringN<N>> {
+        self.iter().collect()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(HEADER + self.data.len());
+        output.extend_from_slice(&(self.len as u32).to_le_bytes());
+        output.extend_from_slice(&(N as u32).to_le_bytes());
+        output.extend_from_slice(&(self.block_size as u32).to_le_bytes());
+        output.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        output.extend_from_slice(&self.data);
+        output
+    }
+
+    /// Reads keys written by `to_bytes`.
+    /// Fails if the header does not match the rest, the size is not N, or a key is invalid or out of order.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < HEADER {
+            return Err(Error::MalformedBuffer)
+        }
+        let field = |i: usize| u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()) as usize;
+        let (len, size, block_size, data_len) = (field(0), field(1), field(2), field(3));
+        if size != N || block_size == 0 || data_len != bytes.len() - HEADER {
+            return Err(Error::MalformedBuffer)
+        }
+        // Every entry takes at least 2 bytes, so a larger count cannot be right and must not be allocated for
+        if len > data_len / 2 {
+            return Err(Error::MalformedBuffer)
+        }
+        let data = bytes[HEADER..].to_vec();
+
+        let mut restarts = Vec::with_capacity(len.div_ceil(block_size));
+        let mut previous = [0; 256];
+        let mut previous_length = 0;
+        let mut buffer = [0; 256];
+        let mut position = 0;
+        for index in 0..len {
+            if data.len() < position + 2 {
+                return Err(Error::MalformedBuffer)
+            }
+            let shared = data[position] as usize;
+            let suffix = data[position + 1] as usize;
+            let restart = index.is_multiple_of(block_size);
+            if restart {
+                restarts.push(position as u32);
+            }
+            if (restart && shared != 0)
+                || shared > previous_length
+                || shared + suffix > KeyStringN::<N>::CAPACITY
+                || data.len() < position + 2 + suffix
+            {
+                return Err(Error::MalformedBuffer)
+            }
+            let mut length = 0;
+            buffer[0..shared].copy_from_slice(&previous[0..shared]);
+            position = decode_entry(&data, position, &mut buffer, &mut length);
+            if index > 0 && buffer[0..length] < previous[0..previous_length] {
+                return Err(Error::MalformedBuffer)
+            }
+            std::str::from_utf8(&buffer[0..length])?;
+            previous = buffer;
+            previous_length = length;
+        }
+        if position != data.len() {
+            return Err(Error::MalformedBuffer)
+        }
+        Ok(FrontCodedKeys { data, restarts, len, block_size })
+    }
+
+}
+
+impl<'a, const N: usize> IntoIterator for &'a FrontCodedKeys<N> {
+    type Item = KeyStringN<N>;
+    type IntoIter = Iter<'a, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Decodes the keys of a FrontCodedKeys in order.
+pub struct Iter<'a, const N: usize> {
+    data: &'a [u8],
+    position: usize,
+    buffer: [u8; 256],
+    length: usize,
+}
+
+impl<const N: usize> Iterator for Iter<'_, N> {
+    type Item = KeyStringN<N>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.data.len() {
+            return None
+        }
+        self.position = decode_entry(self.data, self.position, &mut self.buffer, &mut self.length);
+        Some(KeyStringN::from_bytes_unchecked(&self.buffer[0..self.length]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    fn column() -> Vec<KeyString> {
+        let mut keys: Vec<KeyString> = (0..100)
+            .map(|i| KeyString::from(format!("tenant_{}/table_{}/é{}", i % 7, i % 13, i).as_str()))
+            .chain(["", "", "z"].map(KeyString::from))
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn roundtrip_and_access() {
+        let keys = column();
+        for block_size in [1, 3, 16, 1000] {
+            let coded = FrontCodedKeys::with_block_size(&keys, block_size);
+            assert_eq!(coded.len(), keys.len());
+            assert_eq!(coded.to_vec(), keys);
+            for (index, key) in keys.iter().enumerate() {
+                assert_eq!(coded.get(index), Some(*key));
+                assert_eq!(coded.binary_search(key).map(|i| keys[i]), Ok(*key));
+            }
+            assert_eq!(coded.get(keys.len()), None);
+            for probe in ["", "a", "tenant_3", "tenant_3/table_5/é", "zz"] {
+                let probe = KeyString::from(probe);
+                assert_eq!(coded.binary_search(&probe).is_ok(), keys.binary_search(&probe).is_ok());
+                if let Err(at) = coded.binary_search(&probe) {
+                    assert_eq!(Err(at), keys.binary_search(&probe));
+                }
+            }
+            let bytes = coded.to_bytes();
+            assert_eq!(FrontCodedKeys::from_bytes(&bytes).unwrap(), coded);
+        }
+        let coded = FrontCodedKeys::from_sorted(&keys);
+        assert!(coded.encoded_len() < keys.len() * 20);
+        assert!(FrontCodedKeys::<64>::from_sorted(&[]).iter().next().is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_bytes() {
+        let keys: [KeyString; 3] = ["apple", "apricot", "banana"].map(KeyString::from);
+        let bytes = FrontCodedKeys::with_block_size(&keys, 2).to_bytes();
+        assert!(FrontCodedKeys::<32>::from_bytes(&bytes).is_err());
+        assert!(FrontCodedKeys::<64>::from_bytes(&bytes[0..bytes.len() - 1]).is_err());
+        // A restart that claims to share bytes with the previous key
+        let mut shared = bytes.clone();
+        let banana = shared.len() - 8;
+        shared[banana] = 1;
+        assert!(FrontCodedKeys::<64>::from_bytes(&shared).is_err());
+        // Out of order
+        let mut unsorted = bytes.clone();
+        unsorted[HEADER + 2] = b'z';
+        assert!(FrontCodedKeys::<64>::from_bytes(&unsorted).is_err());
+        // A key longer than the capacity, which must not overrun the decoding buffer
+        let header = |fields: [u32; 4]| fields.iter().flat_map(|field| field.to_le_bytes()).collect::<Vec<u8>>();
+        let mut long = header([2, 256, 2, 262]);
+        long.extend_from_slice(&[0, 3, b'a', b'b', b'c', 3, 255]);
+        long.extend_from_slice(&[b'x'; 255]);
+        assert_eq!(FrontCodedKeys::<256>::from_bytes(&long), Err(Error::MalformedBuffer));
+        // A count far larger than the data could hold, which must not be allocated for
+        assert_eq!(FrontCodedKeys::<64>::from_bytes(&header([u32::MAX, 64, 1, 0])), Err(Error::MalformedBuffer));
+    }
+}
@@ -0,0 +1,247 @@
+//! A fast, non-cryptographic hasher for KeyStrings, and a KeyString that carries its hash with it.
+//! 
+//! `KeyHasher` reads 16 bytes at a time and mixes them with a folded 128 bit multiply, which is far less work
+//! than SipHash for short keys. It has a fixed seed and reads every integer as little endian 64 bit, with 128 bit
+//! integers read as their low then high half, so the same key hashes to the same value in every run and on every
+//! platform, and hashes can be persisted.
+//! It is not resistant to hash flooding, so do not use it for keys chosen by an attacker.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use crate::KeyStringN;
+
+const P0: u64 = 0xa076_1d64_78bd_642f;
+const P1: u64 = 0xe703_7ed1_a0b4_28db;
+const P2: u64 = 0x8ebc_6af0_9c88_c6e3;
+const SEED: u64 = 0x5895_74a3_b2cf_0f77;
+
+#[inline]
+fn fold(a: u64, b: u64) -> u64 {
+    let product = (a as u128).wrapping_mul(b as u128);
+    (product as u64) ^ ((product >> 64) as u64)
+}
+
+#[inline]
+fn read_partial(bytes: &[u8]) -> u64 {
+    let mut buffer = [0u8; 8];
+    buffer[0..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(buffer)
+}
+
+/// A fast hasher with stable output, see the module documentation.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyHasher {
+    state: u64,
+}
+
+impl KeyHasher {
+
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// Different seeds give unrelated hashes for the same input.
+    pub fn with_seed(seed: u64) -> Self {
+        KeyHasher { state: SEED ^ seed }
+    }
+
+}
+
+impl Default for KeyHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for KeyHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut state = self.state;
+        let mut chunks = bytes.chunks_exact(16);
+        for chunk in chunks.by_ref() {
+            let a = u64::from_le_bytes(chunk[0..8].try_into().unwrap());
+            let b = u64::from_le_bytes(chunk[8..16].try_into().unwrap());
+            state = fold(state ^ a ^ P0, b ^ P1);
+        }
+        let rest = chunks.remainder();
+        let split = std::cmp::min(rest.len(), 8);
+        let a = read_partial(&rest[0..split]);
+        let b = read_partial(&rest[split..]);
+        self.state = fold(state ^ a ^ P0, b ^ P1 ^ bytes.len() as u64);
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.state = fold(self.state ^ i ^ P2, P1);
+    }
+
+    /// The default would hash the native endian bytes, so big and little endian platforms would disagree
+    fn write_u128(&mut self, i: u128) {
+        self.write_u64(i as u64);
+        self.write_u64((i >> 64) as u64)
+    }
+
+    /// Always 64 bits, so 32 and 64 bit platforms agree
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64)
+    }
+
+    fn finish(&self) -> u64 {
+        fold(self.state ^ P0, P2)
+    }
+}
+
+/// Builds KeyHashers, for `HashMap::with_hasher`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuildKeyHasher {
+    seed: u64,
+}
+
+impl BuildKeyHasher {
+
+    pub fn with_seed(seed: u64) -> Self {
+        BuildKeyHasher { seed }
+    }
+
+}
+
+impl BuildHasher for BuildKeyHasher {
+    type Hasher = KeyHasher;
+
+    fn build_hasher(&self) -> KeyHasher {
+        KeyHasher::with_seed(self.seed)
+    }
+}
+
+pub type KeyHashMap<K, V> = HashMap<K, V, BuildKeyHasher>;
+pub type KeyHashSet<K> = HashSet<K, BuildKeyHasher>;
+
+impl<const N: usize> KeyStringN<N> {
+
+    /// The hash of the KeyString with KeyHasher. The same in every run and on every platform, so it can be persisted.
+    /// It is fed to the hasher directly, so it does not depend on how std hashes a str,
+    /// but it is the same as what a `BuildKeyHasher` with the default seed gives for this KeyString.
+    pub fn stable_hash(&self) -> u64 {
+        let mut hasher = KeyHasher::new();
+        hasher.write(self.as_bytes());
+        hasher.write_u8(0xff);
+        hasher.finish()
+    }
+
+}
+
+/// A KeyString together with its `stable_hash`, computed once.
+/// Hashing a PrehashedKey only feeds the cached u64 to the hasher, so looking the same key up in many maps does not rehash it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrehashedKey<const N: usize = 64> {
+    hash: u64,
+    key: KeyStringN<N>,
+}
+
+impl<const N: usize> PrehashedKey<N> {
+
+    pub fn new(key: KeyStringN<N>) -> Self {
+        PrehashedKey { hash: key.stable_hash(), key }
+    }
+
+    /// The `stable_hash` computed in `new`. Not called `hash`, so it does not hide `Hash::hash`.
+    pub fn cached_hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn key(&self) -> &KeyStringN<N> {
+        &self.key
+    }
+
+}
+
+impl<const N: usize> Hash for PrehashedKey<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash)
+    }
+}
+
+/// Ordered by the key, so it agrees with the order of KeyString.
+impl<const N: usize> Ord for PrehashedKey<N> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl<const N: usize> PartialOrd for PrehashedKey<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const N: usize> From<KeyStringN<N>> for PrehashedKey<N> {
+    fn from(key: KeyStringN<N>) -> Self {
+        Self::new(key)
+    }
+}
+
+impl<const N: usize> std::ops::Deref for PrehashedKey<N> {
+    type Target = KeyStringN<N>;
+
+    fn deref(&self) -> &KeyStringN<N> {
+        &self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    #[test]
+    fn stable_hashes() {
+        // Pinned, since persisted hashes depend on these never changing
+        assert_eq!(KeyStringN::<64>::from("").stable_hash(), 0xace4_68eb_1cdc_90c9);
+        assert_eq!(KeyStringN::<64>::from("users").stable_hash(), 0x390a_929a_b07a_9cab);
+
+        let key = KeyStringN::<64>::from("orders");
+        assert_eq!(key.stable_hash(), BuildKeyHasher::default().hash_one(key));
+
+        // Only the contents are hashed, so the size of the KeyString does not matter
+        assert_eq!(KeyStringN::<16>::from("users").stable_hash(), KeyStringN::<64>::from("users").stable_hash());
+        assert_ne!(KeyStringN::<64>::from("users").stable_hash(), KeyStringN::<64>::from("users\0").stable_hash());
+        assert_ne!(KeyHasher::with_seed(1).finish(), KeyHasher::new().finish());
+
+        // 128 bit integers must not fall back to the native endian default
+        let wide = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210u128;
+        let mut halves = KeyHasher::new();
+        halves.write_u64(wide as u64);
+        halves.write_u64((wide >> 64) as u64);
+        assert_eq!(BuildKeyHasher::default().hash_one(wide), halves.finish());
+        assert_eq!(BuildKeyHasher::default().hash_one(wide as i128), halves.finish());
+    }
+
+    #[test]
+    fn maps() {
+        let mut map: KeyHashMap<KeyString, usize> = KeyHashMap::default();
+        let mut prehashed: KeyHashMap<PrehashedKey, usize> = KeyHashMap::default();
+        for i in 0..1000 {
+            let key = KeyString::from_i32(i).unwrap();
+            map.insert(key, i as usize);
+            prehashed.insert(PrehashedKey::new(key), i as usize);
+        }
+        let probe = PrehashedKey::new(KeyString::from("500"));
+        assert_eq!(map.get(probe.key()), Some(&500));
+        assert_eq!(prehashed.get(&probe), Some(&500));
+        assert_eq!(probe.cached_hash(), probe.stable_hash());
+        let mut hasher = KeyHasher::new();
+        probe.hash(&mut hasher);
+        assert_eq!(hasher.finish(), BuildKeyHasher::default().hash_one(probe));
+    }
+}
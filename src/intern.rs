@@ -0,0 +1,271 @@
+//! Interning of KeyStrings: each distinct KeyString is stored once and referred to by a 4 byte Symbol.
+//! 
+//! Symbols are handed out in the order keys are first interned, so their Ord is the insertion order.
+//! Call `Interner::sort` to renumber them in KeyString order; after that, comparing Symbols gives the same
+//! result as comparing their keys, until a new key is interned.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::hash::BuildKeyHasher;
+use crate::{Error, KeyStringN};
+
+/// A handle to a KeyString in an Interner. Only meaningful together with the Interner that made it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(pub u32);
+
+impl Symbol {
+
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+
+}
+
+/// Maps KeyStrings to Symbols and back. Resolving a Symbol is an index into a Vec.
+#[derive(Debug, Clone)]
+pub struct Interner<const N: usize = 64> {
+    keys: Vec<KeyStringN<N>>,
+    symbols: HashMap<KeyStringN<N>, Symbol, BuildKeyHasher>,
+    sorted: bool,
+}
+
+impl<const N: usize> Interner<N> {
+
+    pub fn new() -> Self {
+        Interner { keys: Vec::new(), symbols: HashMap::default(), sorted: true }
+    }
+
+    /// The Symbol for key, adding it if it is new.
+    /// 
+    /// # Panics
+    /// Panics if the interner already holds u32::MAX keys.
+    pub fn intern(&mut self, key: KeyStringN<N>) -> Symbol {
+        if let Some(symbol) = self.symbols.get(&key) {
+            return *symbol
+        }
+        let symbol = Symbol(u32::try_from(self.keys.len()).expect("An Interner can hold at most u32::MAX keys"));
+        if let Some(last) = self.keys.last() {
+            self.sorted &= *last < key;
+        }
+        self.keys.push(key);
+        self.symbols.insert(key, symbol);
+        symbol
+    }
+
+    /// Interns every key of a column, appending their Symbols to output in the same order.
+    pub fn intern_column(&mut self, keys: &[KeyStringN<N>], output: &mut Vec<Symbol>) {
+        output.reserve(keys.len());
+        for key in keys {
+            output.push(self.intern(*key));
+        }
+    }
+
+    /// The Symbol for key, if it has been interned.
+    pub fn get(&self, key: &KeyStringN<N>) -> Option<Symbol> {
+        self.symbols.get(key).copied()
+    }
+
+    /// The KeyString for symbol, or None if symbol did not come from this interner.
+    pub fn resolve(&self, symbol: Symbol) -> Option<&KeyStringN<N>> {
+        self.keys.get(symbol.index())
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Every Symbol with its KeyString, in Symbol order.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &KeyStringN<N>)> {
+        self.keys.iter().enumerate().map(|(index, key)| (Symbol(index as u32), key))
+    }
+
+    /// True if Symbol order is currently the same as KeyString order.
+    pub fn is_sorted(&self) -> bool {
+        self.sorted
+    }
+
+    /// Renumbers the Symbols so that their order is the order of their KeyStrings.
+    /// Returns the remapping: the new Symbol for an old Symbol s is `remap[s.index()]`.
+    /// Symbols held outside the interner must be updated with it.
+    pub fn sort(&mut self) -> Vec<Symbol> {
+        let mut order: Vec<usize> = (0..self.keys.len()).collect();
+        order.sort_unstable_by(|a, b| self.keys[*a].cmp(&self.keys[*b]));
+
+        let mut remap = vec![Symbol(0); self.keys.len()];
+        for (new, old) in order.iter().enumerate() {
+            remap[*old] = Symbol(new as u32);
+        }
+        self.keys = order.iter().map(|old| self.keys[*old]).collect();
+        for symbol in self.symbols.values_mut() {
+            *symbol = remap[symbol.index()];
+        }
+        self.sorted = true;
+        remap
+    }
+
+    /// Serializes the symbol table: the number of keys and N as little endian u32s, then the `raw()` buffer of every key in Symbol order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(8 + self.keys.len() * N);
+        output.extend_from_slice(&(self.keys.len() as u32).to_le_bytes());
+        output.extend_from_slice(&(N as u32).to_le_bytes());
+        output.extend_from_slice(KeyStringN::slice_as_bytes(&self.keys));
+        output
+    }
+
+    /// Reads a symbol table written by `to_bytes`, so every key gets back the same Symbol.
+    /// Fails if the header does not match the rest, the size is not N, a key is invalid or a key is there twice.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 8 {
+            return Err(Error::MalformedBuffer)
+        }
+        let count = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        let size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        if size != N || count.checked_mul(N) != Some(bytes.len() - 8) {
+            return Err(Error::MalformedBuffer)
+        }
+        let keys = KeyStringN::<N>::slice_from_bytes(&bytes[8..])?;
+
+        let mut interner = Self::new();
+        interner.keys.reserve(count);
+        interner.symbols.reserve(count);
+        for key in keys {
+            let expected = interner.keys.len();
+            if interner.intern(*key).index() != expected {
+                return Err(Error::MalformedBuffer)
+            }
+        }
+        Ok(interner)
+    }
+
+}
+
+impl<const N: usize> Default for Interner<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An Interner that can be shared between threads, e.g. in an Arc.
+#[derive(Debug, Default)]
+pub struct SharedInterner<const N: usize = 64> {
+    inner: RwLock<Interner<N>>,
+}
+
+impl<const N: usize> SharedInterner<N> {
+
+    pub fn new() -> Self {
+        SharedInterner { inner: RwLock::new(Interner::new()) }
+    }
+
+    pub fn from_interner(interner: Interner<N>) -> Self {
+        SharedInterner { inner: RwLock::new(interner) }
+    }
+
+    /// Takes the write lock only if key is new.
+    pub fn intern(&self, key: KeyStringN<N>) -> Symbol {
+        if let Some(symbol) = self.get(&key) {
+            return symbol
+        }
+        self.inner.write().unwrap().intern(key)
+    }
+
+    pub fn intern_column(&self, keys: &[KeyStringN<N>], output: &mut Vec<Symbol>) {
+        self.inner.write().unwrap().intern_column(keys, output)
+    }
+
+    pub fn get(&self, key: &KeyStringN<N>) -> Option<Symbol> {
+        self.inner.read().unwrap().get(key)
+    }
+
+    /// Returns a copy of the KeyString, since the lock is released before returning.
+    pub fn resolve(&self, symbol: Symbol) -> Option<KeyStringN<N>> {
+        self.inner.read().unwrap().resolve(symbol).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.inner.read().unwrap().to_bytes()
+    }
+
+    pub fn into_interner(self) -> Interner<N> {
+        self.inner.into_inner().unwrap()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    #[test]
+    fn interning() {
+        let mut interner: Interner = Interner::new();
+        let users = interner.intern(KeyString::from("users"));
+        let orders = interner.intern(KeyString::from("orders"));
+        assert_eq!(interner.intern(KeyString::from("users")), users);
+        assert_eq!(interner.resolve(orders).unwrap().as_str(), "orders");
+        assert_eq!(interner.get(&KeyString::from("items")), None);
+        assert_eq!(interner.resolve(Symbol(7)), None);
+
+        let column: Vec<KeyString> = ["b", "a", "b", "c"].iter().map(|s| KeyString::from(*s)).collect();
+        let mut symbols = vec![];
+        interner.intern_column(&column, &mut symbols);
+        assert_eq!(symbols[0], symbols[2]);
+        assert_eq!(interner.len(), 5);
+        assert!(!interner.is_sorted());
+
+        let remap = interner.sort();
+        assert!(interner.is_sorted());
+        let symbols: Vec<Symbol> = symbols.iter().map(|s| remap[s.index()]).collect();
+        for a in &symbols {
+            for b in &symbols {
+                assert_eq!(a.cmp(b), interner.resolve(*a).unwrap().cmp(interner.resolve(*b).unwrap()));
+            }
+        }
+        assert_eq!(interner.get(&KeyString::from("users")), Some(remap[users.index()]));
+    }
+
+    #[test]
+    fn serialization() {
+        let mut interner: Interner<16> = Interner::new();
+        for name in ["users", "orders", "items"] {
+            interner.intern(KeyStringN::from(name));
+        }
+        let bytes = interner.to_bytes();
+        let read = Interner::<16>::from_bytes(&bytes).unwrap();
+        assert!(read.iter().eq(interner.iter()));
+        assert_eq!(read.get(&KeyStringN::from("items")), Some(Symbol(2)));
+
+        assert!(Interner::<64>::from_bytes(&bytes).is_err());
+        assert!(Interner::<16>::from_bytes(&bytes[0..bytes.len() - 1]).is_err());
+        let mut duplicated = bytes.clone();
+        duplicated[8 + 16..8 + 32].copy_from_slice(KeyStringN::<16>::from("users").raw());
+        assert_eq!(Interner::<16>::from_bytes(&duplicated).unwrap_err(), Error::MalformedBuffer);
+    }
+
+    #[test]
+    fn shared() {
+        let interner: std::sync::Arc<SharedInterner> = std::sync::Arc::new(SharedInterner::new());
+        let handles: Vec<_> = (0..4).map(|_| {
+            let interner = interner.clone();
+            std::thread::spawn(move || (0..100).map(|i| interner.intern(KeyString::from_i32(i).unwrap())).collect::<Vec<_>>())
+        }).collect();
+        let results: Vec<Vec<Symbol>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert!(results.iter().all(|r| *r == results[0]));
+        assert_eq!(interner.len(), 100);
+        assert_eq!(interner.resolve(results[0][42]).unwrap().as_str(), "42");
+    }
+}
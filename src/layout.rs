@@ -0,0 +1,94 @@
+use crate::{Error, KeyStringN};
+
+impl<const N: usize> KeyStringN<N> {
+
+    /// Views a buffer of concatenated `raw()` buffers as a slice of KeyStrings without copying.
+    /// Every KeyString is validated once, like `from_raw` does.
+    /// Fails if the length of bytes is not a multiple of N or any of the buffers is invalid.
+    pub fn slice_from_bytes(bytes: &[u8]) -> Result<&[Self], Error> {
+        if !bytes.len().is_multiple_of(N) {
+            return Err(Error::MalformedBuffer)
+        }
+        for raw in bytes.chunks_exact(N) {
+            Self::validate_raw(raw)?;
+        }
+        // Safe since every buffer was just validated
+        Ok(unsafe { Self::slice_from_bytes_unchecked(bytes) })
+    }
+
+    /// Views a buffer of concatenated `raw()` buffers as a slice of KeyStrings without copying or validating.
+    /// 
+    /// # Safety
+    /// The length of bytes must be a multiple of N and every N byte chunk must be a buffer that `from_raw` accepts.
+    pub unsafe fn slice_from_bytes_unchecked(bytes: &[u8]) -> &[Self] {
+        let () = Self::VALID_SIZE;
+        debug_assert!(bytes.len().is_multiple_of(N));
+        // KeyStringN<N> is repr(transparent) over [u8; N], so it has the same size and an alignment of 1
+        std::slice::from_raw_parts(bytes.as_ptr() as *const Self, bytes.len() / N)
+    }
+
+    /// Views a slice of KeyStrings as the concatenation of their `raw()` buffers without copying.
+    pub fn slice_as_bytes(keys: &[Self]) -> &[u8] {
+        // Safe since KeyStringN<N> is repr(transparent) over [u8; N], which has no padding and no invalid bit patterns
+        unsafe { std::slice::from_raw_parts(keys.as_ptr() as *const u8, std::mem::size_of_val(keys)) }
+    }
+
+}
+
+/// The empty KeyString is all zeros.
+#[cfg(feature = "bytemuck")]
+unsafe impl<const N: usize> bytemuck::Zeroable for KeyStringN<N> {}
+
+#[cfg(feature = "bytemuck")]
+unsafe impl<const N: usize> bytemuck::NoUninit for KeyStringN<N> {}
+
+/// Lets `bytemuck::checked::try_cast_slice` cast bytes into KeyStrings, with the same validation as `from_raw`.
+#[cfg(feature = "bytemuck")]
+unsafe impl<const N: usize> bytemuck::CheckedBitPattern for KeyStringN<N> {
+    type Bits = [u8; N];
+
+    fn is_valid_bit_pattern(bits: &Self::Bits) -> bool {
+        Self::validate_raw(bits).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    #[test]
+    fn slice_casts() {
+        let keys: Vec<KeyString> = ["users", "orders", "ünïcode"].iter().map(|s| KeyString::from(*s)).collect();
+        let bytes = KeyString::slice_as_bytes(&keys);
+        assert_eq!(bytes.len(), 3 * 64);
+        assert_eq!(&bytes[64..128], keys[1].raw());
+
+        let cast = KeyStringN::<64>::slice_from_bytes(bytes).unwrap();
+        assert_eq!(cast, &keys[..]);
+        assert_eq!(cast.as_ptr() as *const u8, bytes.as_ptr());
+
+        assert_eq!(KeyStringN::<64>::slice_from_bytes(&bytes[1..]), Err(Error::MalformedBuffer));
+        let mut corrupt = bytes.to_vec();
+        corrupt[64 + 63] = 200;
+        assert!(KeyStringN::<64>::slice_from_bytes(&corrupt).is_err());
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn bytemuck_casts() {
+        let keys = [KeyStringN::<16>::from("a"), KeyStringN::<16>::from("b")];
+        let bytes: &[u8] = bytemuck::cast_slice(&keys);
+        let cast: &[KeyStringN<16>] = bytemuck::checked::try_cast_slice(bytes).unwrap();
+        assert_eq!(cast, &keys[..]);
+        assert!(bytemuck::checked::try_cast_slice::<u8, KeyStringN<16>>(&[1u8; 16]).is_err());
+    }
+
+    #[cfg(feature = "zerocopy")]
+    #[test]
+    fn zerocopy_bytes() {
+        use zerocopy::IntoBytes;
+        let keys = [KeyStringN::<16>::from("a"), KeyStringN::<16>::from("b")];
+        assert_eq!(keys.as_bytes(), KeyStringN::slice_as_bytes(&keys));
+    }
+}
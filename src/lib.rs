@@ -1,190 +1,435 @@
-use std::{num::{ParseFloatError, ParseIntError}, str::Utf8Error};
-
-#[derive(Clone, Copy, Hash, PartialEq)]
-pub struct KeyString {
-    inner: [u8;64],
+use std::str::Utf8Error;
+
+// Lets the derive macros refer to this crate as ::hallib_rs from inside it as well
+extern crate self as hallib_rs;
+
+pub mod ascii;
+pub mod case;
+pub mod decode;
+pub mod dict;
+mod error;
+mod format;
+pub mod front_coded;
+pub mod hash;
+pub mod intern;
+mod layout;
+pub mod map;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+pub mod natural;
+#[cfg(feature = "normalization")]
+pub mod normalize;
+mod numeric;
+pub mod record;
+mod simd;
+#[cfg(feature = "serde")]
+mod serde_impls;
+pub mod trie;
+pub mod tuple;
+pub use error::Error;
+
+/// A KeyString of the default size of 64 bytes. This is the type to use unless a different size is needed,
+/// and since it has no generic parameter `KeyString::from("...")` works without annotations.
+pub type KeyString = KeyStringN<64>;
+
+/// A fixed capacity, stack allocated string that is always valid utf8.
+/// 
+/// `KeyStringN<N>` is N bytes, e.g. `KeyStringN<16>` for short column names or `KeyStringN<256>` for file names.
+/// Plain `KeyString` is `KeyStringN<64>` and keeps meaning what it always has.
+/// 
+/// N must be between 1 and 256, since the length is stored in a single byte, so a KeyStringN holds at most 255 bytes.
+/// That is not enough for long file paths (Linux allows 4096 bytes), which need a `String` or `PathBuf`.
+/// Any other N is a compile error as soon as a KeyStringN of that size is created or read:
+/// 
+/// ```compile_fail
+/// let keys = hallib_rs::KeyStringN::<1000>::slice_from_bytes(&[]);
+/// ```
+/// 
+/// # Layout
+/// The buffer holds the string bytes from the start, zero padding after them, and the length of the string
+/// in the last byte. This means a `KeyStringN<N>` can hold N-1 bytes, `len()` is a single read, and strings
+/// containing '\0' round trip exactly. The padding is always zero so two KeyStrings are equal exactly when their buffers are.
+/// 
+/// Older versions stored the string zero padded with no length byte. Buffers written by `raw()` in that layout
+/// should be read with `KeyStringN::from_zero_padded` and written back out with `raw()` to migrate them.
+/// Buffers in the current layout are read with `KeyStringN::from_raw`.
+/// 
+/// The old 64 byte KeyString held up to 64 bytes of text, one more than `KeyString` holds now, so
+/// `KeyString::from_zero_padded` fails on legacy buffers that use all 64 bytes and `KeyString::from` cuts
+/// a 64 byte &str to 63. Data that may contain full length keys has to migrate into a `KeyStringN<65>`.
+/// 
+/// `KeyStringN<N>` is `#[repr(transparent)]` over `[u8; N]`, so it is exactly N bytes with an alignment of 1,
+/// and a slice of KeyStrings is the concatenation of their `raw()` buffers.
+/// `KeyStringN::slice_from_bytes` and `KeyStringN::slice_as_bytes` cast between the two without copying.
+/// The `bytemuck` feature implements `CheckedBitPattern` so `bytemuck::checked` casts work too.
+/// The `zerocopy` feature only derives the writing side (`IntoBytes`), since zerocopy cannot check the
+/// KeyString invariants when reading; use `slice_from_bytes` for that.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "zerocopy", derive(zerocopy::IntoBytes, zerocopy::Immutable, zerocopy::KnownLayout))]
+#[repr(transparent)]
+pub struct KeyStringN<const N: usize> {
+    inner: [u8;N],
 }
 
-impl std::fmt::Debug for KeyString {
+impl<const N: usize> std::fmt::Debug for KeyStringN<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("KeyString").field("inner", &self.as_str()).finish()
     }
 }
 
-impl std::fmt::Display for KeyString {
+impl<const N: usize> std::fmt::Display for KeyStringN<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let text = bytes_to_str(&self.inner).expect(&format!("A KeyString should always be valid utf8.\nThe KeyString that was just attempted to Display was:\n{:x?}", self.inner));
-        write!(f, "{}", text)
+        write!(f, "{}", self.as_str())
     }   
 }
 
-impl Default for KeyString {
-    fn default() -> Self {
-        Self { inner: [0;64] }
+/// Hashes only the contents, the same way a str does, so the padding is not hashed and the size of the KeyString does not matter.
+impl<const N: usize> std::hash::Hash for KeyStringN<N> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
     }
 }
 
-/// Turns a &str into a KeyString. If the &str has more than 64 bytes, the last bytes will be cut.
-impl From<&str> for KeyString {
-    fn from(s: &str) -> Self {
-
-        let mut inner = [0u8;64];
-
-        let mut min = std::cmp::min(s.len(), 64);
-        inner[0..min].copy_from_slice(&s.as_bytes()[0..min]);
-
-        loop {
-            if min == 0 {break}
-            match std::str::from_utf8(&inner[0..min]) {
-                Ok(_) => break,
-                Err(_) => min -= 1,
-            }
-        }
-
-        KeyString {
-            inner
-        }
+impl<const N: usize> AsRef<str> for KeyStringN<N> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
 
+impl<const N: usize> Default for KeyStringN<N> {
+    fn default() -> Self {
+        Self::empty()
     }
 }
 
+/// Turns a &str into a KeyString. If the &str has more than N-1 bytes, the last bytes will be cut.
+/// Use `KeyString::try_from_str` to get an error instead, or `KeyString::from_str_truncating` to find out how much was cut.
+impl<const N: usize> From<&str> for KeyStringN<N> {
+    fn from(s: &str) -> Self {
+        Self::from_str_truncating(s).0
+    }
+}
 
-impl TryFrom<&[u8]> for KeyString {
-    type Error = Utf8Error;
+/// Turns the bytes of a string into a KeyString. Same as `KeyString::from_utf8`.
+/// To read a buffer produced by `raw()`, use `KeyString::from_raw` instead.
+impl<const N: usize> TryFrom<&[u8]> for KeyStringN<N> {
+    type Error = Error;
 
     fn try_from(s: &[u8]) -> Result<Self, Self::Error> {
-        let mut inner = [0u8;64];
+        Self::from_utf8(s)
+    }
+}
 
-        let min = std::cmp::min(s.len(), 64);
-        inner[0..min].copy_from_slice(&s[0..min]);
+/// Same as `KeyString::try_from_str`.
+impl<const N: usize> std::str::FromStr for KeyStringN<N> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from_str(s)
+    }
+}
 
-        match std::str::from_utf8(&inner) {
-            Ok(_) => {
-                Ok(KeyString {inner})
-            },
-            Err(e) => Err(e),
+/// Panics if the chars do not fit. Use `try_extend` to handle that case.
+impl<const N: usize> Extend<char> for KeyStringN<N> {
+    fn extend<T: IntoIterator<Item = char>>(&mut self, iter: T) {
+        if let Err(e) = self.try_extend(iter) {
+            panic!("{}", e)
         }
     }
 }
 
-impl Eq for KeyString {}
+/// Panics if the strings do not fit. Use `push` to handle that case.
+impl<'a, const N: usize> Extend<&'a str> for KeyStringN<N> {
+    fn extend<T: IntoIterator<Item = &'a str>>(&mut self, iter: T) {
+        for s in iter {
+            if let Err(e) = self.push(s) {
+                panic!("{}", e)
+            }
+        }
+    }
+}
 
-impl Ord for KeyString {
+/// Orders like `as_str()` does, but finds the first differing byte 16 or 32 bytes at a time with SSE2/AVX2 on x86_64
+/// and 8 bytes at a time elsewhere.
+impl<const N: usize> Ord for KeyStringN<N> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.as_str().cmp(other.as_str())
+        simd::order_from_difference(self, other, simd::first_difference(&self.inner, &other.inner))
     }
 }
 
-impl PartialOrd for KeyString {
+impl<const N: usize> PartialOrd for KeyStringN<N> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.as_str().cmp(other.as_str()))
+        Some(self.cmp(other))
     }
 }
 
 impl KeyString {
 
+    /// Creates an empty KeyString with the default size.
+    /// Use `KeyStringN::<N>::default()` for other sizes.
     pub fn new() -> Self {
-        KeyString {
-            inner: [0u8; 64]
-        }
+        Self::empty()
     }
 
-    pub fn len(&self) -> usize {
-        let mut output = 0;
-        for byte in self.inner {
-            match byte {
-                0 => break,
-                _ => output += 1,
-            }
+}
+
+impl<const N: usize> KeyStringN<N> {
+
+    const VALID_SIZE: () = assert!(N >= 1 && N <= 256, "The size of a KeyString must be between 1 and 256 bytes");
+
+    /// The maximum number of bytes a `KeyStringN<N>` can hold. The last byte of the buffer stores the length.
+    /// Checks the size too, so every path that validates a buffer against CAPACITY rejects an N over 256.
+    pub const CAPACITY: usize = {
+        let () = Self::VALID_SIZE;
+        N - 1
+    };
+
+    const fn empty() -> Self {
+        let () = Self::VALID_SIZE;
+        KeyStringN {
+            inner: [0u8; N]
         }
+    }
+
+    /// The caller must make sure that bytes are valid utf8 and no longer than CAPACITY
+    fn from_bytes_unchecked(bytes: &[u8]) -> Self {
+        let mut output = Self::empty();
+        output.inner[0..bytes.len()].copy_from_slice(bytes);
+        output.inner[N-1] = bytes.len() as u8;
         output
     }
 
-    pub fn push(&mut self, s: &str) {
+    /// Turns a &str into a KeyString at compile time, for use in `const` and `static` items.
+    /// Panics if it has more than CAPACITY bytes, which is a compile error when evaluated in a const context.
+    /// The `keystring!` macro wraps this and always evaluates it at compile time.
+    pub const fn from_const(s: &str) -> Self {
+        let bytes = s.as_bytes();
+        assert!(bytes.len() <= Self::CAPACITY, "The string does not fit in the KeyString");
+        let mut output = Self::empty();
+        let mut index = 0;
+        while index < bytes.len() {
+            output.inner[index] = bytes[index];
+            index += 1;
+        }
+        output.inner[N-1] = bytes.len() as u8;
+        output
+    }
 
-        if self.len() + s.len() > 64 {
-            return
+    /// Turns a &str into a KeyString. Fails if it has more than CAPACITY bytes.
+    pub fn try_from_str(s: &str) -> Result<Self, Error> {
+        if s.len() > Self::CAPACITY {
+            return Err(Error::CapacityExceeded { attempted: s.len(), capacity: Self::CAPACITY })
         }
+        Ok(Self::from_bytes_unchecked(s.as_bytes()))
+    }
+
+    /// Turns a &str into a KeyString, cutting the last chars if it has more than CAPACITY bytes.
+    /// Also returns the number of bytes that were cut.
+    pub fn from_str_truncating(s: &str) -> (Self, usize) {
+        let mut output = Self::empty();
+        let dropped = output.push_truncating(s);
+        (output, dropped)
+    }
+
+    /// Turns the bytes of a string into a KeyString. Fails if they are not utf8 or there are more than CAPACITY of them.
+    pub fn from_utf8(bytes: &[u8]) -> Result<Self, Error> {
+        let s = std::str::from_utf8(bytes)?;
+        Self::try_from_str(s)
+    }
 
-        let mut end_index = 0;
-        for (index, byte) in self.inner.iter().enumerate() {
-            if byte == &0 {
-                end_index = index+1;
+    /// Turns the bytes of a string into a KeyString, replacing invalid utf8 sequences with U+FFFD like `String::from_utf8_lossy`.
+    /// Fails if the result has more than CAPACITY bytes.
+    pub fn from_utf8_lossy(bytes: &[u8]) -> Result<Self, Error> {
+        let mut output = Self::empty();
+        let mut attempted = 0;
+        for chunk in bytes.utf8_chunks() {
+            attempted += chunk.valid().len();
+            if !chunk.invalid().is_empty() {
+                attempted += char::REPLACEMENT_CHARACTER.len_utf8();
             }
         }
-
-        for (index, byte) in s.as_bytes().iter().enumerate() {
-            self.inner[index+end_index] = *byte;
+        if attempted > Self::CAPACITY {
+            return Err(Error::CapacityExceeded { attempted, capacity: Self::CAPACITY })
+        }
+        for chunk in bytes.utf8_chunks() {
+            output.push(chunk.valid())?;
+            if !chunk.invalid().is_empty() {
+                output.push_char(char::REPLACEMENT_CHARACTER)?;
+            }
         }
+        Ok(output)
+    }
 
+    /// Turns the bytes of a string into a KeyString without checking that they are utf8.
+    /// 
+    /// # Safety
+    /// The bytes must be valid utf8. Every other function on KeyString relies on it.
+    /// 
+    /// # Panics
+    /// Panics if there are more than CAPACITY bytes.
+    pub unsafe fn from_utf8_unchecked(bytes: &[u8]) -> Self {
+        assert!(bytes.len() <= Self::CAPACITY, "{} bytes do not fit in a KeyString with capacity {}", bytes.len(), Self::CAPACITY);
+        Self::from_bytes_unchecked(bytes)
     }
 
-    pub fn as_str(&self) -> &str {
-        // This is safe since an enforced invariant of KeyString is that it is utf8
-        unsafe { std::str::from_utf8_unchecked(&self.inner[0..self.len()]) }
+    /// Reads a buffer produced by `raw()`.
+    /// Fails if the buffer is the wrong size, the length byte is out of range, the padding is not zero
+    /// or the contents are not utf8.
+    pub fn from_raw(raw: &[u8]) -> Result<Self, Error> {
+        Self::validate_raw(raw)?;
+        let len = raw[N-1] as usize;
+        Ok(Self::from_bytes_unchecked(&raw[0..len]))
     }
 
-    pub fn as_bytes(&self) -> &[u8] {
-        &self.inner[0..self.len()]
+    /// Checks that raw is a valid KeyStringN<N> buffer, see `from_raw`.
+    fn validate_raw(raw: &[u8]) -> Result<(), Error> {
+        if raw.len() != N {
+            return Err(Error::MalformedBuffer)
+        }
+        let len = raw[N-1] as usize;
+        if len > Self::CAPACITY {
+            return Err(Error::CapacityExceeded { attempted: len, capacity: Self::CAPACITY })
+        }
+        if raw[len..N-1].iter().any(|byte| *byte != 0) {
+            return Err(Error::MalformedBuffer)
+        }
+        std::str::from_utf8(&raw[0..len])?;
+        Ok(())
     }
 
-    pub fn raw(&self) -> &[u8] {
-        &self.inner
+    /// Reads a buffer written in the old zero padded layout, where the string ends at the first 0 byte.
+    /// Fails if there is anything but 0 bytes after the string, or if the contents are not utf8 or do not fit in CAPACITY.
+    /// Use this to migrate data written by older versions of this crate. A full legacy buffer of N bytes only fits
+    /// in a `KeyStringN<{N + 1}>`, e.g. old 64 byte keys that use every byte need `KeyStringN::<65>::from_zero_padded`.
+    pub fn from_zero_padded(buffer: &[u8]) -> Result<Self, Error> {
+        let len = buffer.iter().position(|byte| *byte == 0).unwrap_or(buffer.len());
+        if len > Self::CAPACITY {
+            return Err(Error::CapacityExceeded { attempted: len, capacity: Self::CAPACITY })
+        }
+        if buffer[len..].iter().any(|byte| *byte != 0) {
+            return Err(Error::InteriorNul { position: len })
+        }
+        std::str::from_utf8(&buffer[0..len])?;
+        Ok(Self::from_bytes_unchecked(&buffer[0..len]))
     }
 
-    /// These functions may panic and should only be called if you are certain that the KeyString contains a valid number
-    pub fn to_i32(&self) -> i32 {
-        self.as_str().parse::<i32>().unwrap()
+    pub const fn len(&self) -> usize {
+        self.inner[N-1] as usize
     }
 
-    /// These functions may panic and should only be called if you are certain that the KeyString contains a valid number
-    pub fn to_f32(&self) -> f32 {
-        self.as_str().parse::<f32>().unwrap()
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
-    pub fn to_i32_checked(&self) -> Result<i32, ParseIntError> {
-        self.as_str().parse::<i32>()
+    /// Appends s to the end of the KeyString.
+    /// If s does not fit, the KeyString is left unchanged and an Error::CapacityExceeded is returned.
+    pub fn push(&mut self, s: &str) -> Result<(), Error> {
+
+        let len = self.len();
+        if len + s.len() > Self::CAPACITY {
+            return Err(Error::CapacityExceeded { attempted: len + s.len(), capacity: Self::CAPACITY })
+        }
+
+        self.inner[len..len+s.len()].copy_from_slice(s.as_bytes());
+        self.inner[N-1] = (len + s.len()) as u8;
+
+        Ok(())
+
     }
 
-    pub fn to_f32_checked(&self) -> Result<f32, ParseFloatError> {
-        self.as_str().parse::<f32>()
+    /// Appends as much of s as fits, cutting at a char boundary like `From<&str>` does.
+    /// Returns the number of bytes of s that were dropped.
+    pub fn push_truncating(&mut self, s: &str) -> usize {
+
+        let mut min = std::cmp::min(s.len(), Self::CAPACITY - self.len());
+        while !s.is_char_boundary(min) {
+            min -= 1;
+        }
+
+        // Cannot fail since min fits and is on a char boundary
+        let _ = self.push(&s[0..min]);
+
+        s.len() - min
+
     }
 
-}
+    /// Appends a single char. Fails like `push` if it does not fit.
+    pub fn push_char(&mut self, c: char) -> Result<(), Error> {
+        let mut buffer = [0u8; 4];
+        self.push(c.encode_utf8(&mut buffer))
+    }
 
+    /// Appends bytes that must be valid utf8. Fails like `push` if they do not fit.
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let s = std::str::from_utf8(bytes)?;
+        self.push(s)
+    }
 
-/// Removes the trailing 0 bytes from a str created from a byte buffer
-pub fn bytes_to_str(bytes: &[u8]) -> Result<&str, Utf8Error> {
-    let mut index: usize = 0;
-    let len = bytes.len();
-    let mut start: usize = 0;
-    
-    while index < len {
-        if bytes[index] != 0 {
-            break
+    /// Appends every char of the iterator.
+    /// If a char does not fit, the chars before it are kept and an Error::CapacityExceeded is returned.
+    pub fn try_extend<I: IntoIterator<Item = char>>(&mut self, iter: I) -> Result<(), Error> {
+        for c in iter {
+            self.push_char(c)?;
         }
-        index += 1;
-        start += 1;
+        Ok(())
+    }
+
+    pub const fn as_str(&self) -> &str {
+        // This is safe since an enforced invariant of KeyString is that it is utf8
+        unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
     }
 
-    if bytes.is_empty() {
-        return Ok("")
+    pub const fn as_bytes(&self) -> &[u8] {
+        self.inner.split_at(self.len()).0
     }
 
-    if start >= bytes.len()-1 {
-        return Ok("")
+    /// The whole buffer, including the padding and the length byte. Read it back with `KeyString::from_raw`.
+    pub fn raw(&self) -> &[u8] {
+        &self.inner
     }
 
-    let mut stop: usize = start;
-    while index < len {
-        if bytes[index] == 0 {
-            break
+    /// Converts to a KeyString with a different capacity.
+    /// Fails if the contents do not fit in the new capacity.
+    pub fn to_capacity<const M: usize>(&self) -> Result<KeyStringN<M>, Error> {
+        if self.len() > KeyStringN::<M>::CAPACITY {
+            return Err(Error::CapacityExceeded { attempted: self.len(), capacity: KeyStringN::<M>::CAPACITY })
         }
-        index += 1;
-        stop += 1;
+        Ok(KeyStringN::from_bytes_unchecked(self.as_bytes()))
     }
 
-    std::str::from_utf8(&bytes[start..stop])
+    /// Converts to a KeyString with a different capacity, cutting the last characters if they do not fit.
+    pub fn to_capacity_truncating<const M: usize>(&self) -> KeyStringN<M> {
+        KeyStringN::from(self.as_str())
+    }
+
+}
+
+
+/// Builds a KeyString from a string literal at compile time. A literal that does not fit is a compile error.
+/// 
+/// `keystring!("users")` makes a `KeyString` with the default size, `keystring!("id", 16)` makes a `KeyStringN<16>`.
+/// The result is a constant expression, so it can be used to define `const` and `static` KeyStrings,
+/// and those constants can be used as `match` patterns.
+/// 
+/// ```compile_fail
+/// let too_long = hallib_rs::keystring!("this literal is longer than fifteen bytes", 16);
+/// ```
+#[macro_export]
+macro_rules! keystring {
+    ($s:expr) => {
+        $crate::keystring!($s, 64)
+    };
+    ($s:expr, $n:expr) => {{
+        const KEY: $crate::KeyStringN<$n> = $crate::KeyStringN::<$n>::from_const($s);
+        KEY
+    }};
+}
+
+
+/// Removes the trailing 0 bytes from a str created from a byte buffer.
+/// Leading and interior 0 bytes are kept, see `decode::nul_padded`, which this wraps, and the rest of the `decode` module.
+pub fn bytes_to_str(bytes: &[u8]) -> Result<&str, Utf8Error> {
+    std::str::from_utf8(&bytes[decode::nul_padded_span(bytes)])
 }
 
 
@@ -202,4 +447,145 @@ mod tests {
         let result = add(2, 2);
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn default_size_without_annotations() {
+        let k = KeyString::from("x");
+        assert_eq!(k.as_str(), "x");
+        let parsed = KeyString::try_from("users".as_bytes()).unwrap();
+        assert_eq!(KeyString::CAPACITY, 63);
+        assert_eq!(std::mem::size_of_val(&parsed), 64);
+    }
+
+    #[test]
+    fn keystring_capacities() {
+        let short: KeyStringN<8> = KeyStringN::from("column_name");
+        assert_eq!(short.as_str(), "column_");
+
+        let default = KeyString::new();
+        assert_eq!(default.raw().len(), 64);
+
+        let path: KeyStringN<256> = KeyStringN::from("a".repeat(100).as_str());
+        assert_eq!(path.len(), 100);
+        assert_eq!(path.to_capacity::<64>(), Err(Error::CapacityExceeded { attempted: 100, capacity: 63 }));
+        assert_eq!(path.to_capacity_truncating::<64>().len(), 63);
+
+        let widened: KeyStringN<256> = short.to_capacity().unwrap();
+        assert_eq!(widened.as_str(), "column_");
+    }
+
+    #[test]
+    fn keystring_layout() {
+        let mut key = KeyString::new();
+        key.push("a\0b").unwrap();
+        key.push("é").unwrap();
+        assert_eq!(key.as_str(), "a\0bé");
+        assert_eq!(key.len(), 5);
+        assert_eq!(key.raw()[63], 5);
+
+        let round_trip = KeyString::from_raw(key.raw()).unwrap();
+        assert_eq!(round_trip, key);
+
+        let mut bad = [0u8; 64];
+        bad[63] = 64;
+        assert!(KeyStringN::<64>::from_raw(&bad).is_err());
+        bad[63] = 1;
+        bad[5] = b'x';
+        assert_eq!(KeyStringN::<64>::from_raw(&bad), Err(Error::MalformedBuffer));
+
+        let mut legacy = [0u8; 64];
+        legacy[0..5].copy_from_slice(b"users");
+        let migrated = KeyStringN::<64>::from_zero_padded(&legacy).unwrap();
+        assert_eq!(migrated.as_str(), "users");
+        assert!(KeyStringN::<64>::from_zero_padded(&[b'a'; 64]).is_err());
+        legacy[10] = b'x';
+        assert_eq!(KeyStringN::<64>::from_zero_padded(&legacy), Err(Error::InteriorNul { position: 5 }));
+
+        // A legacy key that used all 64 bytes no longer fits a KeyString, but migrates into a KeyStringN<65>
+        let full: Vec<u8> = (0..64).map(|i| b'a' + i % 26).collect();
+        assert_eq!(KeyString::from_zero_padded(&full), Err(Error::CapacityExceeded { attempted: 64, capacity: 63 }));
+        let migrated = KeyStringN::<65>::from_zero_padded(&full).unwrap();
+        assert_eq!(migrated.as_bytes(), full.as_slice());
+        assert_eq!(KeyStringN::<65>::from_raw(migrated.raw()), Ok(migrated));
+        assert_eq!(KeyString::from(migrated.as_str()).len(), 63);
+    }
+
+    #[test]
+    fn keystring_push() {
+        let mut key: KeyStringN<8> = KeyStringN::from("abc");
+        key.push("de").unwrap();
+        assert_eq!(key.as_str(), "abcde");
+        assert_eq!(key.push("fgh"), Err(Error::CapacityExceeded { attempted: 8, capacity: 7 }));
+        assert_eq!(key.as_str(), "abcde");
+
+        assert_eq!(key.push_truncating("fé"), 2);
+        assert_eq!(key.as_str(), "abcdef");
+        assert!(key.push_char('é').is_err());
+        key.push_char('g').unwrap();
+        assert_eq!(key.as_str(), "abcdefg");
+
+        let mut key: KeyStringN<8> = KeyStringN::default();
+        assert!(key.push_bytes(&[0xff]).is_err());
+        key.extend(["ab", "c"]);
+        key.extend("de".chars());
+        assert_eq!(key.as_str(), "abcde");
+        assert!(key.try_extend("xyz".chars()).is_err());
+        assert_eq!(key.as_str(), "abcdexy");
+    }
+
+    #[test]
+    fn errors() {
+        fn parse_sum(a: &KeyString, b: &KeyString) -> Result<f32, Error> {
+            Ok(a.to_i32_checked()? as f32 + b.to_f32_checked()?)
+        }
+        assert_eq!(parse_sum(&KeyString::from("2"), &KeyString::from("0.5")), Ok(2.5));
+        assert!(matches!(parse_sum(&KeyString::from("x"), &KeyString::from("0.5")), Err(Error::ParseInt(_))));
+        assert!(matches!(parse_sum(&KeyString::from("2"), &KeyString::from("x")), Err(Error::ParseFloat(_))));
+        assert!(matches!(KeyStringN::<64>::try_from(&[0xffu8][..]), Err(Error::InvalidUtf8(_))));
+    }
+
+    #[test]
+    fn constructors() {
+        let long = "a".repeat(70);
+        assert_eq!(KeyStringN::<64>::try_from_str(&long), Err(Error::CapacityExceeded { attempted: 70, capacity: 63 }));
+        assert_eq!(KeyStringN::<64>::try_from_str("users").unwrap().as_str(), "users");
+        assert_eq!("users".parse::<KeyString>().unwrap().as_str(), "users");
+
+        let (key, dropped) = KeyStringN::<4>::from_str_truncating("abé");
+        assert_eq!((key.as_str(), dropped), ("ab", 2));
+
+        assert!(KeyStringN::<64>::try_from(long.as_bytes()).is_err());
+        assert!(KeyStringN::<64>::from_utf8(b"ab\xff").is_err());
+        assert_eq!(KeyStringN::<64>::from_utf8_lossy(b"ab\xffc").unwrap().as_str(), "ab\u{FFFD}c");
+        assert!(KeyStringN::<6>::from_utf8_lossy(b"ab\xffc").is_err());
+
+        let key = unsafe { KeyStringN::<64>::from_utf8_unchecked(b"users") };
+        assert_eq!(key.as_str(), "users");
+    }
+
+    #[test]
+    fn const_keystrings() {
+        const USERS: KeyString = keystring!("users");
+        const ORDERS: KeyString = KeyString::from_const("orders");
+        static ID: KeyStringN<16> = keystring!("id", 16);
+
+        assert_eq!(USERS.as_str(), "users");
+        assert_eq!(ID.len(), 2);
+
+        let table = |key: KeyString| match key {
+            USERS => 1,
+            ORDERS => 2,
+            _ => 0,
+        };
+        assert_eq!(table(KeyString::from("orders")), 2);
+        assert_eq!(table(KeyString::from("items")), 0);
+    }
+
+    #[test]
+    fn bytes_to_str_edge_cases() {
+        assert_eq!(bytes_to_str(b""), Ok(""));
+        assert_eq!(bytes_to_str(b"\0\0a"), Ok("\0\0a"));
+        assert_eq!(bytes_to_str(b"a"), Ok("a"));
+        assert_eq!(bytes_to_str(b"ab\0\0"), Ok("ab"));
+    }
 }
@@ -0,0 +1,194 @@
+//! An ordered map keyed by KeyString, with the scans that indexes need: prefix, range, floor and ceiling.
+//! 
+//! It is a B-tree (std's BTreeMap) with the KeyStrings stored inline in its nodes, so a lookup touches a few
+//! nodes of contiguous keys instead of chasing a pointer per key. Iteration is in `impl Ord for KeyString` order.
+
+use std::collections::BTreeMap;
+use std::ops::{Bound, RangeBounds};
+
+use crate::KeyStringN;
+
+impl<const N: usize> KeyStringN<N> {
+
+    /// The smallest KeyString that is greater than every string starting with self, which is the exclusive
+    /// upper bound for a prefix scan. It is self with its last char replaced by the next char.
+    /// 
+    /// Returns None if there is no such KeyString: self is empty or only char::MAX, or the next char needs
+    /// more bytes than are left. In those cases scan without an upper bound and stop at the first key that does not start with self.
+    pub fn prefix_successor(&self) -> Option<Self> {
+        let mut text = self.as_str();
+        while let Some(last) = text.chars().next_back() {
+            let prefix = &text[0..text.len() - last.len_utf8()];
+            let next = match last {
+                char::MAX => None,
+                '\u{D7FF}' => Some('\u{E000}'),
+                _ => char::from_u32(last as u32 + 1),
+            };
+            match next {
+                Some(next) => {
+                    let mut output = Self::try_from_str(prefix).ok()?;
+                    output.push_char(next).ok()?;
+                    return Some(output)
+                },
+                None => text = prefix,
+            }
+        }
+        None
+    }
+
+}
+
+/// An ordered map from `KeyStringN<N>` to V. See the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyMap<V, const N: usize = 64> {
+    tree: BTreeMap<KeyStringN<N>, V>,
+}
+
+impl<V, const N: usize> Default for KeyMap<V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V, const N: usize> KeyMap<V, N> {
+
+    pub fn new() -> Self {
+        KeyMap { tree: BTreeMap::new() }
+    }
+
+    /// Returns the old value if the key was already there.
+    pub fn insert(&mut self, key: KeyStringN<N>, value: V) -> Option<V> {
+        self.tree.insert(key, value)
+    }
+
+    pub fn get(&self, key: &KeyStringN<N>) -> Option<&V> {
+        self.tree.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &KeyStringN<N>) -> Option<&mut V> {
+        self.tree.get_mut(key)
+    }
+
+    pub fn contains_key(&self, key: &KeyStringN<N>) -> bool {
+        self.tree.contains_key(key)
+    }
+
+    pub fn remove(&mut self, key: &KeyStringN<N>) -> Option<V> {
+        self.tree.remove(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Every entry in key order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&KeyStringN<N>, &V)> {
+        self.tree.iter()
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &KeyStringN<N>> {
+        self.tree.keys()
+    }
+
+    /// The entries whose keys are in range, in key order, e.g. `map.range(a..b)`.
+    pub fn range<R: RangeBounds<KeyStringN<N>>>(&self, range: R) -> impl DoubleEndedIterator<Item = (&KeyStringN<N>, &V)> {
+        self.tree.range(range)
+    }
+
+    /// The entries whose keys start with prefix, in key order.
+    pub fn prefix_scan<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a KeyStringN<N>, &'a V)> + 'a {
+        let bounds = match KeyStringN::<N>::try_from_str(prefix) {
+            Ok(start) => {
+                let end = match start.prefix_successor() {
+                    Some(end) => Bound::Excluded(end),
+                    None => Bound::Unbounded,
+                };
+                Some((Bound::Included(start), end))
+            },
+            // A prefix longer than the capacity cannot match any key
+            Err(_) => None,
+        };
+        bounds.into_iter()
+            .flat_map(move |bounds| self.tree.range(bounds))
+            .take_while(move |(key, _)| key.as_str().starts_with(prefix))
+    }
+
+    /// The entry with the greatest key that is less than or equal to key.
+    pub fn floor(&self, key: &KeyStringN<N>) -> Option<(&KeyStringN<N>, &V)> {
+        self.tree.range(..=*key).next_back()
+    }
+
+    /// The entry with the least key that is greater than or equal to key.
+    pub fn ceiling(&self, key: &KeyStringN<N>) -> Option<(&KeyStringN<N>, &V)> {
+        self.tree.range(*key..).next()
+    }
+
+    pub fn first(&self) -> Option<(&KeyStringN<N>, &V)> {
+        self.tree.first_key_value()
+    }
+
+    pub fn last(&self) -> Option<(&KeyStringN<N>, &V)> {
+        self.tree.last_key_value()
+    }
+
+}
+
+impl<V, const N: usize> FromIterator<(KeyStringN<N>, V)> for KeyMap<V, N> {
+    fn from_iter<I: IntoIterator<Item = (KeyStringN<N>, V)>>(iter: I) -> Self {
+        KeyMap { tree: iter.into_iter().collect() }
+    }
+}
+
+impl<V, const N: usize> IntoIterator for KeyMap<V, N> {
+    type Item = (KeyStringN<N>, V);
+    type IntoIter = std::collections::btree_map::IntoIter<KeyStringN<N>, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tree.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    fn key(s: &str) -> KeyString {
+        KeyString::from(s)
+    }
+
+    #[test]
+    fn successors() {
+        assert_eq!(key("users/").prefix_successor(), Some(key("users0")));
+        assert_eq!(key("a\u{10FFFF}").prefix_successor(), Some(key("b")));
+        assert_eq!(key("\u{D7FF}").prefix_successor(), Some(key("\u{E000}")));
+        assert_eq!(key("").prefix_successor(), None);
+        assert_eq!(key("\u{10FFFF}").prefix_successor(), None);
+        assert_eq!(KeyStringN::<4>::from("ab\u{7f}").prefix_successor(), None);
+    }
+
+    #[test]
+    fn scans() {
+        let map: KeyMap<usize> = ["users/1", "users/2", "users", "users0", "orders/1", "user", "users/\u{10FFFF}"]
+            .iter().enumerate().map(|(i, s)| (key(s), i)).collect();
+
+        let prefixed: Vec<&str> = map.prefix_scan("users/").map(|(k, _)| k.as_str()).collect();
+        assert_eq!(prefixed, vec!["users/1", "users/2", "users/\u{10FFFF}"]);
+        assert_eq!(map.prefix_scan("").count(), map.len());
+        assert_eq!(map.prefix_scan(&"x".repeat(100)).count(), 0);
+
+        let ranged: Vec<&str> = map.range(key("user")..key("users/2")).map(|(k, _)| k.as_str()).collect();
+        assert_eq!(ranged, vec!["user", "users", "users/1"]);
+
+        assert_eq!(map.floor(&key("users/15")).unwrap().0.as_str(), "users/1");
+        assert_eq!(map.ceiling(&key("users/15")).unwrap().0.as_str(), "users/2");
+        assert_eq!(map.floor(&key("a")), None);
+        assert_eq!(map.ceiling(&key("users1")), None);
+        assert_eq!(map.first().unwrap().0.as_str(), "orders/1");
+        assert!(map.keys().zip(map.keys().skip(1)).all(|(a, b)| a < b));
+    }
+}
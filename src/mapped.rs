@@ -0,0 +1,188 @@
+//! Memory mapped files of KeyStrings.
+//! 
+//! A key file is a 16 byte header followed by KeyStrings in the `raw()` layout, back to back. The header is the
+//! magic bytes `HKEY`, then the format version, N and a reserved 0 as little endian u32s. The number of keys is
+//! not stored, it follows from the file length, so a file can be appended to without rewriting the header.
+
+use crate::KeyStringN;
+use memmap2::Mmap;
+use std::{fs::{File, OpenOptions}, io::{self, BufWriter, Read, Seek, SeekFrom, Write}, ops::Deref, path::Path};
+
+pub const MAGIC: [u8; 4] = *b"HKEY";
+pub const VERSION: u32 = 1;
+pub const HEADER_LEN: usize = 16;
+
+fn header<const N: usize>() -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[0..4].copy_from_slice(&MAGIC);
+    header[4..8].copy_from_slice(&VERSION.to_le_bytes());
+    header[8..12].copy_from_slice(&(N as u32).to_le_bytes());
+    header
+}
+
+fn invalid_data(message: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Checks the header and returns the keys part of the file.
+fn body<const N: usize>(bytes: &[u8]) -> io::Result<&[u8]> {
+    if bytes.len() < HEADER_LEN || bytes[0..HEADER_LEN] != header::<N>() {
+        return Err(invalid_data(format!("not a version {} key file of KeyStringN<{}>", VERSION, N)))
+    }
+    let body = &bytes[HEADER_LEN..];
+    if !body.len().is_multiple_of(N) {
+        return Err(invalid_data("key file length is not a whole number of keys"))
+    }
+    Ok(body)
+}
+
+/// A read-only key file mapped into memory, which derefs to `[KeyStringN<N>]` without copying.
+#[derive(Debug)]
+pub struct MappedKeys<const N: usize = 64> {
+    map: Mmap,
+}
+
+impl<const N: usize> MappedKeys<N> {
+
+    /// Maps a key file and validates every key once.
+    /// 
+    /// # Safety
+    /// The file must not be modified or truncated while it is mapped, by this process or any other.
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let map = Mmap::map(&File::open(path)?)?;
+        KeyStringN::<N>::slice_from_bytes(body::<N>(&map)?).map_err(invalid_data)?;
+        Ok(MappedKeys { map })
+    }
+
+    /// Maps a trusted key file, checking only the header and length, so opening does not read every page.
+    /// 
+    /// # Safety
+    /// As for `open`, and every key in the file must be a buffer that `KeyString::from_raw` accepts,
+    /// e.g. because it was written by a `KeyFileWriter`.
+    pub unsafe fn open_unchecked(path: impl AsRef<Path>) -> io::Result<Self> {
+        let map = Mmap::map(&File::open(path)?)?;
+        body::<N>(&map)?;
+        Ok(MappedKeys { map })
+    }
+
+    pub fn keys(&self) -> &[KeyStringN<N>] {
+        // The body was checked when the file was opened, or the caller vouched for it
+        unsafe { KeyStringN::slice_from_bytes_unchecked(&self.map[HEADER_LEN..]) }
+    }
+
+}
+
+impl<const N: usize> Deref for MappedKeys<N> {
+    type Target = [KeyStringN<N>];
+
+    fn deref(&self) -> &Self::Target {
+        self.keys()
+    }
+}
+
+impl<const N: usize> AsRef<[KeyStringN<N>]> for MappedKeys<N> {
+    fn as_ref(&self) -> &[KeyStringN<N>] {
+        self.keys()
+    }
+}
+
+/// Writes KeyStrings to a key file that MappedKeys can open.
+#[derive(Debug)]
+pub struct KeyFileWriter<const N: usize = 64> {
+    file: BufWriter<File>,
+}
+
+impl<const N: usize> KeyFileWriter<N> {
+
+    /// Creates a new key file, or truncates an existing one, and writes the header.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&header::<N>())?;
+        Ok(KeyFileWriter { file })
+    }
+
+    /// Opens an existing key file to add keys at the end. Fails if its header is for another version or N.
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut existing = [0; HEADER_LEN];
+        file.read_exact(&mut existing).map_err(|_| invalid_data("key file is shorter than its header"))?;
+        if existing != header::<N>() {
+            return Err(invalid_data(format!("not a version {} key file of KeyStringN<{}>", VERSION, N)))
+        }
+        let end = file.seek(SeekFrom::End(0))? as usize;
+        if !(end - HEADER_LEN).is_multiple_of(N) {
+            return Err(invalid_data("key file length is not a whole number of keys"))
+        }
+        Ok(KeyFileWriter { file: BufWriter::new(file) })
+    }
+
+    pub fn write(&mut self, key: &KeyStringN<N>) -> io::Result<()> {
+        self.file.write_all(key.raw())
+    }
+
+    pub fn write_all(&mut self, keys: &[KeyStringN<N>]) -> io::Result<()> {
+        self.file.write_all(KeyStringN::slice_as_bytes(keys))
+    }
+
+    /// Flushes everything written and syncs it to disk.
+    pub fn finish(self) -> io::Result<()> {
+        self.file.into_inner().map_err(|e| e.into_error())?.sync_all()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("hallib-rs-{}-{}.keys", std::process::id(), name))
+    }
+
+    #[test]
+    fn write_append_and_map() {
+        let path = temp_path("roundtrip");
+        let keys: Vec<KeyString> = (0..1000).map(|i| KeyString::from(format!("key_{}", i).as_str())).collect();
+        let mut writer = KeyFileWriter::create(&path).unwrap();
+        writer.write_all(&keys[0..600]).unwrap();
+        writer.finish().unwrap();
+        let mut writer = KeyFileWriter::append(&path).unwrap();
+        for key in &keys[600..] {
+            writer.write(key).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), (HEADER_LEN + 1000 * 64) as u64);
+
+        let mapped = unsafe { MappedKeys::<64>::open(&path) }.unwrap();
+        assert_eq!(mapped.keys(), keys.as_slice());
+        assert_eq!(mapped[999].as_str(), "key_999");
+        let trusted = unsafe { MappedKeys::<64>::open_unchecked(&path) }.unwrap();
+        assert_eq!(trusted.len(), 1000);
+
+        assert!(unsafe { MappedKeys::<32>::open(&path) }.is_err());
+        assert!(KeyFileWriter::<32>::append(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let path = temp_path("bad");
+        let mut bytes = header::<64>().to_vec();
+        bytes.extend_from_slice(KeyStringN::<64>::from("fine").raw());
+        let mut invalid = KeyStringN::<64>::from("ab").raw().to_vec();
+        invalid[0] = 0xFF;
+        bytes.extend_from_slice(&invalid);
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(unsafe { MappedKeys::<64>::open(&path) }.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        std::fs::write(&path, &bytes[0..bytes.len() - 1]).unwrap();
+        assert!(unsafe { MappedKeys::<64>::open_unchecked(&path) }.is_err());
+        assert!(KeyFileWriter::<64>::append(&path).is_err());
+
+        bytes[4] = 2;
+        std::fs::write(&path, &bytes[0..HEADER_LEN]).unwrap();
+        assert!(unsafe { MappedKeys::<64>::open_unchecked(&path) }.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+//! Natural ordering, where runs of digits compare by their value, so "item2" sorts before "item10".
+//! 
+//! A `-` or `+` right before a digit run is its sign if it is at the start of the string or follows whitespace,
+//! so "-10" sorts before "-2" and "3", while "item-5" is "item", "-" and 5.
+//! Digit runs sort before any other char. Fractions are not recognised, "1.5" is 1, "." and 5.
+//! 
+//! Strings that are equal by value, like "007" and "7" or "-0" and "0", are ordered by their bytes,
+//! so the order is total and two strings are only equal if they are identical.
+
+use std::cmp::Ordering;
+
+use crate::{KeyString, KeyStringN};
+
+#[derive(Debug, Clone, Copy)]
+enum Token<'a> {
+    Char(char),
+    /// digits has no leading zeros, so it is empty for zero
+    Number { negative: bool, digits: &'a str },
+}
+
+struct Tokens<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let rest = &self.text[self.position..];
+        let mut chars = rest.chars();
+        let first = chars.next()?;
+
+        let at_boundary = self.text[..self.position].chars().next_back().is_none_or(char::is_whitespace);
+        let signed = (first == '-' || first == '+') && at_boundary && rest[1..].starts_with(|c: char| c.is_ascii_digit());
+
+        if !first.is_ascii_digit() && !signed {
+            self.position += first.len_utf8();
+            return Some(Token::Char(first))
+        }
+
+        let sign_len = if signed { 1 } else { 0 };
+        let run_len = rest[sign_len..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - sign_len);
+        let run = &rest[sign_len..sign_len + run_len];
+        let digits = run.trim_start_matches('0');
+        self.position += sign_len + run_len;
+        Some(Token::Number { negative: first == '-' && !digits.is_empty(), digits })
+    }
+}
+
+fn tokens(text: &str) -> Tokens<'_> {
+    Tokens { text, position: 0 }
+}
+
+fn compare_tokens(a: Token, b: Token) -> Ordering {
+    match (a, b) {
+        (Token::Char(a), Token::Char(b)) => a.cmp(&b),
+        (Token::Number { .. }, Token::Char(_)) => Ordering::Less,
+        (Token::Char(_), Token::Number { .. }) => Ordering::Greater,
+        (Token::Number { negative: a_negative, digits: a_digits }, Token::Number { negative: b_negative, digits: b_digits }) => {
+            let magnitude = a_digits.len().cmp(&b_digits.len()).then_with(|| a_digits.cmp(b_digits));
+            match (a_negative, b_negative) {
+                (false, false) => magnitude,
+                (true, true) => magnitude.reverse(),
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+            }
+        },
+    }
+}
+
+/// Compares two strings in natural order, see the module documentation.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_tokens = tokens(a);
+    let mut b_tokens = tokens(b);
+    loop {
+        match (a_tokens.next(), b_tokens.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_token), Some(b_token)) => match compare_tokens(a_token, b_token) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            },
+        }
+    }
+}
+
+impl<const N: usize> KeyStringN<N> {
+
+    /// Compares in natural order, where "item2" sorts before "item10". See the `natural` module.
+    pub fn natural_cmp(&self, other: &Self) -> Ordering {
+        natural_cmp(self.as_str(), other.as_str())
+    }
+
+}
+
+/// Wraps a string so that Ord, Eq and Hash use natural order, e.g. for sorting, BTreeMap keys and `binary_search`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NaturalOrd<T = KeyString>(pub T);
+
+impl<T: AsRef<str>> std::hash::Hash for NaturalOrd<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.as_ref().hash(state)
+    }
+}
+
+impl<T: AsRef<str>> PartialEq for NaturalOrd<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_ref() == other.0.as_ref()
+    }
+}
+
+impl<T: AsRef<str>> Eq for NaturalOrd<T> {}
+
+impl<T: AsRef<str>> Ord for NaturalOrd<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        natural_cmp(self.0.as_ref(), other.0.as_ref())
+    }
+}
+
+impl<T: AsRef<str>> PartialOrd for NaturalOrd<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_order() {
+        let mut keys: Vec<KeyString> = ["item10", "item2", "item02", "item1", "-10", "3", "-2", "0", "-0", "item-5", "item", "a b-1", "a b-3"]
+            .iter().map(|s| KeyString::from(*s)).collect();
+        keys.sort_by(|a, b| a.natural_cmp(b));
+        let sorted: Vec<&str> = keys.iter().map(|k| k.as_str()).collect();
+        assert_eq!(sorted, vec!["-10", "-2", "-0", "0", "3", "a b-1", "a b-3", "item", "item1", "item02", "item2", "item10", "item-5"]);
+
+        let index = keys.binary_search_by(|k| k.natural_cmp(&KeyString::from("item2")));
+        assert_eq!(index, Ok(10));
+        assert_eq!(keys.binary_search_by(|k| k.natural_cmp(&KeyString::from("item3"))), Err(11));
+
+        let mut wrapped: Vec<NaturalOrd> = keys.iter().rev().map(|k| NaturalOrd(*k)).collect();
+        wrapped.sort();
+        assert!(wrapped.iter().map(|n| n.0).eq(keys.iter().copied()));
+        assert_ne!(NaturalOrd("07"), NaturalOrd("7"));
+    }
+}
@@ -0,0 +1,184 @@
+//! Unicode normalization of KeyStrings, so that keys that look the same also compare equal.
+//! "é" as a single code point and "e" followed by a combining accent are different bytes, but the same in NFC.
+//! 
+//! The normalization tables are compiled into the binary, so nothing is loaded at runtime.
+//! Normalizing can make a string longer, and a result that no longer fits is an Error::CapacityExceeded, never truncated.
+
+use std::marker::PhantomData;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{Error, KeyStringN};
+
+/// A Unicode normalization form.
+pub trait NormalizationForm {
+    /// Appends s in this normal form to output. Fails without truncating if it does not fit.
+    fn normalize_into<const N: usize>(s: &str, output: &mut KeyStringN<N>) -> Result<(), Error>;
+
+    fn is_normalized(s: &str) -> bool;
+}
+
+/// Canonical composition, the form to use when only the encoding of a char should not matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Nfc;
+
+/// Compatibility composition, which also folds compatibility chars like "ﬁ" into "fi" and "①" into "1".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Nfkc;
+
+fn push_chars<const N: usize>(chars: impl Iterator<Item = char>, output: &mut KeyStringN<N>) -> Result<(), Error> {
+    let start = output.len();
+    let mut chars = chars;
+    for c in chars.by_ref() {
+        if output.push_char(c).is_err() {
+            let attempted = output.len() + c.len_utf8() + chars.map(char::len_utf8).sum::<usize>();
+            output.inner[start..N-1].fill(0);
+            output.inner[N-1] = start as u8;
+            return Err(Error::CapacityExceeded { attempted, capacity: KeyStringN::<N>::CAPACITY })
+        }
+    }
+    Ok(())
+}
+
+impl NormalizationForm for Nfc {
+    fn normalize_into<const N: usize>(s: &str, output: &mut KeyStringN<N>) -> Result<(), Error> {
+        push_chars(s.nfc(), output)
+    }
+
+    fn is_normalized(s: &str) -> bool {
+        unicode_normalization::is_nfc(s)
+    }
+}
+
+impl NormalizationForm for Nfkc {
+    fn normalize_into<const N: usize>(s: &str, output: &mut KeyStringN<N>) -> Result<(), Error> {
+        push_chars(s.nfkc(), output)
+    }
+
+    fn is_normalized(s: &str) -> bool {
+        unicode_normalization::is_nfkc(s)
+    }
+}
+
+impl<const N: usize> KeyStringN<N> {
+
+    /// Returns a copy in the normal form F, e.g. `key.normalized::<Nfc>()`. Fails if it no longer fits.
+    pub fn normalized<F: NormalizationForm>(&self) -> Result<Self, Error> {
+        if F::is_normalized(self.as_str()) {
+            return Ok(*self)
+        }
+        let mut output = Self::empty();
+        F::normalize_into(self.as_str(), &mut output)?;
+        Ok(output)
+    }
+
+    pub fn is_normalized<F: NormalizationForm>(&self) -> bool {
+        F::is_normalized(self.as_str())
+    }
+
+}
+
+/// A KeyString that is always in the normal form F (NFC unless specified), because every constructor normalizes.
+/// Two NormalizedKeyStrings with the same form compare equal exactly when their text is canonically (or compatibly for NFKC) equivalent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NormalizedKeyString<const N: usize = 64, F = Nfc> {
+    key: KeyStringN<N>,
+    form: PhantomData<F>,
+}
+
+impl<const N: usize, F: NormalizationForm> NormalizedKeyString<N, F> {
+
+    /// Normalizes s. Fails if the result does not fit.
+    pub fn new(s: &str) -> Result<Self, Error> {
+        let mut key = KeyStringN::empty();
+        F::normalize_into(s, &mut key)?;
+        Ok(NormalizedKeyString { key, form: PhantomData })
+    }
+
+    /// Normalizes key. Fails if the result does not fit.
+    pub fn from_keystring(key: KeyStringN<N>) -> Result<Self, Error> {
+        Ok(NormalizedKeyString { key: key.normalized::<F>()?, form: PhantomData })
+    }
+
+    pub fn as_keystring(&self) -> &KeyStringN<N> {
+        &self.key
+    }
+
+    pub fn into_keystring(self) -> KeyStringN<N> {
+        self.key
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.key.as_str()
+    }
+
+}
+
+impl<const N: usize, F> std::ops::Deref for NormalizedKeyString<N, F> {
+    type Target = KeyStringN<N>;
+
+    fn deref(&self) -> &KeyStringN<N> {
+        &self.key
+    }
+}
+
+impl<const N: usize, F> AsRef<str> for NormalizedKeyString<N, F> {
+    fn as_ref(&self) -> &str {
+        self.key.as_str()
+    }
+}
+
+impl<const N: usize, F> std::fmt::Display for NormalizedKeyString<N, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.key)
+    }
+}
+
+impl<const N: usize, F: NormalizationForm> TryFrom<&str> for NormalizedKeyString<N, F> {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
+impl<const N: usize, F: NormalizationForm> TryFrom<KeyStringN<N>> for NormalizedKeyString<N, F> {
+    type Error = Error;
+
+    fn try_from(key: KeyStringN<N>) -> Result<Self, Self::Error> {
+        Self::from_keystring(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalization() {
+        let composed = KeyStringN::<64>::from("caf\u{e9}");
+        let decomposed = KeyStringN::<64>::from("cafe\u{301}");
+        assert_ne!(composed, decomposed);
+        assert_eq!(decomposed.normalized::<Nfc>().unwrap(), composed);
+        assert!(composed.is_normalized::<Nfc>());
+        assert!(!decomposed.is_normalized::<Nfc>());
+
+        let a: NormalizedKeyString = NormalizedKeyString::new("cafe\u{301}").unwrap();
+        let b: NormalizedKeyString = NormalizedKeyString::from_keystring(composed).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 5);
+
+        let ligature: NormalizedKeyString<64, Nfkc> = NormalizedKeyString::new("\u{fb01}le").unwrap();
+        assert_eq!(ligature.as_str(), "file");
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        // "ǆ" is 2 bytes but its NFKC form "dž" is 3, while "①" is 3 bytes and becomes "1"
+        let key = KeyStringN::<8>::from("ǆǆǆ");
+        assert_eq!(key.len(), 6);
+        assert_eq!(key.normalized::<Nfkc>(), Err(Error::CapacityExceeded { attempted: 9, capacity: 7 }));
+        assert!(NormalizedKeyString::<8, Nfkc>::new("ǆǆǆ").is_err());
+        assert_eq!(NormalizedKeyString::<8, Nfkc>::new("①②").unwrap().as_str(), "12");
+    }
+}
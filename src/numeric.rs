@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use crate::{Error, KeyStringN};
+
+impl<const N: usize> KeyStringN<N> {
+
+    fn from_display<T: std::fmt::Display>(value: &T) -> Result<Self, Error> {
+        Self::from_fmt(format_args!("{}", value))
+    }
+
+    /// Formats a float with Display, falling back to scientific notation if that does not fit.
+    /// Both forms parse back to the same value.
+    fn from_float<T: std::fmt::Display + std::fmt::LowerExp>(value: T) -> Result<Self, Error> {
+        match Self::from_display(&value) {
+            Ok(output) => Ok(output),
+            Err(e) => Self::from_fmt(format_args!("{:e}", value)).map_err(|_| e),
+        }
+    }
+
+    /// Parses the KeyString as any type that implements FromStr, like `str::parse`.
+    pub fn parse<T: FromStr>(&self) -> Result<T, T::Err> {
+        self.as_str().parse::<T>()
+    }
+
+    /// Writes the shortest representation that parses back to the same value. Fails if it does not fit.
+    pub fn from_f32(value: f32) -> Result<Self, Error> {
+        Self::from_float(value)
+    }
+
+    /// Writes the shortest representation that parses back to the same value. Fails if it does not fit.
+    pub fn from_f64(value: f64) -> Result<Self, Error> {
+        Self::from_float(value)
+    }
+
+}
+
+macro_rules! parse_functions {
+    ($($t:ty => $to:ident, $to_checked:ident;)*) => {
+        impl<const N: usize> KeyStringN<N> {
+            $(
+                /// These functions may panic and should only be called if you are certain that the KeyString contains a valid value
+                pub fn $to(&self) -> $t {
+                    self.as_str().parse::<$t>().unwrap()
+                }
+
+                pub fn $to_checked(&self) -> Result<$t, Error> {
+                    Ok(self.parse::<$t>()?)
+                }
+            )*
+        }
+    };
+}
+
+parse_functions! {
+    i8 => to_i8, to_i8_checked;
+    i16 => to_i16, to_i16_checked;
+    i32 => to_i32, to_i32_checked;
+    i64 => to_i64, to_i64_checked;
+    i128 => to_i128, to_i128_checked;
+    isize => to_isize, to_isize_checked;
+    u8 => to_u8, to_u8_checked;
+    u16 => to_u16, to_u16_checked;
+    u32 => to_u32, to_u32_checked;
+    u64 => to_u64, to_u64_checked;
+    u128 => to_u128, to_u128_checked;
+    usize => to_usize, to_usize_checked;
+    f32 => to_f32, to_f32_checked;
+    f64 => to_f64, to_f64_checked;
+    bool => to_bool, to_bool_checked;
+}
+
+macro_rules! from_functions {
+    ($($t:ty => $from:ident;)*) => {
+        impl<const N: usize> KeyStringN<N> {
+            $(
+                /// Writes the value without allocating. Fails if it does not fit.
+                pub fn $from(value: $t) -> Result<Self, Error> {
+                    Self::from_display(&value)
+                }
+            )*
+        }
+    };
+}
+
+from_functions! {
+    i8 => from_i8;
+    i16 => from_i16;
+    i32 => from_i32;
+    i64 => from_i64;
+    i128 => from_i128;
+    isize => from_isize;
+    u8 => from_u8;
+    u16 => from_u16;
+    u32 => from_u32;
+    u64 => from_u64;
+    u128 => from_u128;
+    usize => from_usize;
+    bool => from_bool;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let key = KeyStringN::<64>::from_i64(-1234567890123).unwrap();
+        assert_eq!(key.as_str(), "-1234567890123");
+        assert_eq!(key.to_i64(), -1234567890123);
+        assert!(key.to_u64_checked().is_err());
+        assert!(key.to_i8_checked().is_err());
+
+        assert_eq!(KeyStringN::<64>::from_u128(u128::MAX).unwrap().to_u128(), u128::MAX);
+        assert!(KeyStringN::<64>::from_bool(true).unwrap().to_bool());
+        assert!(matches!(KeyStringN::<64>::from("yes").to_bool_checked(), Err(Error::ParseBool(_))));
+        assert_eq!(KeyStringN::<4>::from_u32(12345), Err(Error::CapacityExceeded { attempted: 5, capacity: 3 }));
+
+        assert_eq!(KeyStringN::<64>::from_f64(0.1).unwrap().as_str(), "0.1");
+        let huge = KeyStringN::<64>::from_f64(1e300).unwrap();
+        assert_eq!(huge.as_str(), "1e300");
+        assert_eq!(huge.to_f64(), 1e300);
+        assert_eq!(KeyStringN::<64>::from_f32(-2.5).unwrap().parse::<f32>(), Ok(-2.5));
+        assert_eq!(KeyStringN::<64>::from("x").parse::<char>(), Ok('x'));
+        assert_eq!(KeyStringN::<64>::from("10.0.0.1").parse::<std::net::Ipv4Addr>(), Ok(std::net::Ipv4Addr::new(10, 0, 0, 1)));
+        assert!(KeyStringN::<64>::from("10.0.0").parse::<std::net::Ipv4Addr>().is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! Fixed width records: structs that are encoded as their fields back to back, each field taking a fixed number of bytes.
+//! 
+//! Implement `FixedRecord` by hand, or enable the `derive` feature and use `#[derive(FixedRecord)]`.
+//! 
+//! ```ignore
+//! #[derive(FixedRecord)]
+//! #[fixed_record(endian = "big")]
+//! struct Row {
+//!     name: KeyString,
+//!     id: i64,
+//!     score: f32,
+//! }
+//! 
+//! let mut buffer = [0u8; Row::SIZE];
+//! row.encode_into(&mut buffer)?;
+//! let row = Row::decode_from(&buffer)?;
+//! ```
+
+use crate::{Error, KeyStringN};
+
+#[cfg(feature = "derive")]
+pub use hallib_rs_derive::FixedRecord;
+
+/// The byte order of the numbers in a record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+/// A value that always takes exactly SIZE bytes in a record.
+pub trait FixedField: Sized {
+    const SIZE: usize;
+
+    /// Writes the value into buffer, which is exactly SIZE bytes.
+    fn encode_field(&self, buffer: &mut [u8], endian: Endian);
+
+    /// Reads the value from buffer, which is exactly SIZE bytes.
+    fn decode_field(buffer: &[u8], endian: Endian) -> Result<Self, Error>;
+}
+
+/// A struct that is encoded as its fields back to back, SIZE bytes in total.
+pub trait FixedRecord: Sized {
+    const SIZE: usize;
+    const ENDIAN: Endian = Endian::Little;
+
+    /// Writes the record into the first SIZE bytes of buffer.
+    /// Fails with Error::CapacityExceeded if buffer is shorter than SIZE.
+    fn encode_into(&self, buffer: &mut [u8]) -> Result<(), Error>;
+
+    /// Reads the record from the first SIZE bytes of buffer.
+    /// Fails with Error::MalformedBuffer if buffer is shorter than SIZE, or with the error of the first field that does not decode.
+    fn decode_from(buffer: &[u8]) -> Result<Self, Error>;
+}
+
+macro_rules! number_fields {
+    ($($t:ty),*) => {
+        $(
+            impl FixedField for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                fn encode_field(&self, buffer: &mut [u8], endian: Endian) {
+                    match endian {
+                        Endian::Little => buffer.copy_from_slice(&self.to_le_bytes()),
+                        Endian::Big => buffer.copy_from_slice(&self.to_be_bytes()),
+                    }
+                }
+
+                fn decode_field(buffer: &[u8], endian: Endian) -> Result<Self, Error> {
+                    let bytes = buffer.try_into().map_err(|_| Error::MalformedBuffer)?;
+                    match endian {
+                        Endian::Little => Ok(<$t>::from_le_bytes(bytes)),
+                        Endian::Big => Ok(<$t>::from_be_bytes(bytes)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+number_fields!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64);
+
+/// A single byte that must be 0 or 1.
+impl FixedField for bool {
+    const SIZE: usize = 1;
+
+    fn encode_field(&self, buffer: &mut [u8], _endian: Endian) {
+        buffer[0] = *self as u8;
+    }
+
+    fn decode_field(buffer: &[u8], _endian: Endian) -> Result<Self, Error> {
+        match buffer {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(Error::MalformedBuffer),
+        }
+    }
+}
+
+/// Raw bytes, copied as they are.
+impl<const N: usize> FixedField for [u8; N] {
+    const SIZE: usize = N;
+
+    fn encode_field(&self, buffer: &mut [u8], _endian: Endian) {
+        buffer.copy_from_slice(self);
+    }
+
+    fn decode_field(buffer: &[u8], _endian: Endian) -> Result<Self, Error> {
+        buffer.try_into().map_err(|_| Error::MalformedBuffer)
+    }
+}
+
+/// The `raw()` buffer of the KeyString. The length byte does not depend on endianness.
+impl<const N: usize> FixedField for KeyStringN<N> {
+    const SIZE: usize = N;
+
+    fn encode_field(&self, buffer: &mut [u8], _endian: Endian) {
+        buffer.copy_from_slice(self.raw());
+    }
+
+    fn decode_field(buffer: &[u8], _endian: Endian) -> Result<Self, Error> {
+        KeyStringN::from_raw(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Manual {
+        name: KeyStringN<16>,
+        id: u32,
+    }
+
+    impl FixedRecord for Manual {
+        const SIZE: usize = 16 + 4;
+
+        fn encode_into(&self, buffer: &mut [u8]) -> Result<(), Error> {
+            if buffer.len() < Self::SIZE {
+                return Err(Error::CapacityExceeded { attempted: Self::SIZE, capacity: buffer.len() })
+            }
+            self.name.encode_field(&mut buffer[0..16], Self::ENDIAN);
+            self.id.encode_field(&mut buffer[16..20], Self::ENDIAN);
+            Ok(())
+        }
+
+        fn decode_from(buffer: &[u8]) -> Result<Self, Error> {
+            if buffer.len() < Self::SIZE {
+                return Err(Error::MalformedBuffer)
+            }
+            Ok(Manual {
+                name: KeyStringN::decode_field(&buffer[0..16], Self::ENDIAN)?,
+                id: u32::decode_field(&buffer[16..20], Self::ENDIAN)?,
+            })
+        }
+    }
+
+    #[test]
+    fn manual_record() {
+        let row = Manual { name: KeyStringN::from("users"), id: 7 };
+        let mut buffer = [0u8; Manual::SIZE];
+        row.encode_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[16..20], &[7, 0, 0, 0]);
+        assert_eq!(Manual::decode_from(&buffer).unwrap(), row);
+        assert!(row.encode_into(&mut [0u8; 10]).is_err());
+        assert_eq!(Manual::decode_from(&buffer[0..10]), Err(Error::MalformedBuffer));
+    }
+
+    #[cfg(feature = "derive")]
+    mod derived {
+        use super::super::*;
+        use crate::KeyString;
+
+        #[derive(FixedRecord, Debug, PartialEq)]
+        struct Row {
+            name: KeyString,
+            id: i64,
+            score: f32,
+            active: bool,
+        }
+
+        #[derive(FixedRecord, Debug, PartialEq)]
+        #[fixed_record(endian = "big")]
+        struct BigEndian(u16, KeyStringN<8>);
+
+        #[test]
+        fn derived_records() {
+            assert_eq!(Row::SIZE, 64 + 8 + 4 + 1);
+            let row = Row { name: KeyString::from("users"), id: -3, score: 1.5, active: true };
+            let mut buffer = [0u8; Row::SIZE * 2];
+            row.encode_into(&mut buffer[Row::SIZE..]).unwrap();
+            assert_eq!(Row::decode_from(&buffer[Row::SIZE..]).unwrap(), row);
+
+            buffer[Row::SIZE * 2 - 1] = 2;
+            assert_eq!(Row::decode_from(&buffer[Row::SIZE..]), Err(Error::MalformedBuffer));
+
+            let record = BigEndian(0x0102, KeyStringN::from("id"));
+            let mut buffer = [0u8; BigEndian::SIZE];
+            record.encode_into(&mut buffer).unwrap();
+            assert_eq!(&buffer[0..2], &[1, 2]);
+            assert_eq!(BigEndian::decode_from(&buffer).unwrap(), record);
+        }
+
+        /// The generated code must not pick up an Ok or Err defined where the derive is used
+        mod shadowed {
+            use super::super::super::*;
+
+            #[allow(dead_code)]
+            enum Shadow { Ok, Err }
+            #[allow(unused_imports)]
+            use Shadow::{Ok, Err};
+
+            #[derive(FixedRecord, Debug, PartialEq)]
+            struct Pair(u8, u32);
+
+            #[test]
+            fn shadowed_result_constructors() {
+                let mut buffer = [0u8; Pair::SIZE];
+                Pair(1, 2).encode_into(&mut buffer).unwrap();
+                assert_eq!(Pair::decode_from(&buffer).unwrap(), Pair(1, 2));
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::KeyStringN;
+
+/// Serializes as a string in human readable formats (JSON, TOML, ...) and as length prefixed bytes in binary formats (bincode, MessagePack, ...).
+impl<const N: usize> Serialize for KeyStringN<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(self.as_str())
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+struct KeyStringVisitor<const N: usize>;
+
+impl<'de, const N: usize> Visitor<'de> for KeyStringVisitor<N> {
+    type Value = KeyStringN<N>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a utf8 string of at most {} bytes", KeyStringN::<N>::CAPACITY)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        KeyStringN::try_from_str(v).map_err(|_| E::invalid_length(v.len(), &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        if v.len() > KeyStringN::<N>::CAPACITY {
+            return Err(E::invalid_length(v.len(), &self))
+        }
+        KeyStringN::from_utf8(v).map_err(|_| E::invalid_value(de::Unexpected::Bytes(v), &self))
+    }
+
+    /// Some binary formats hand bytes over as a sequence of u8
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut buffer = [0u8; N];
+        let mut len = 0;
+        while let Some(byte) = seq.next_element::<u8>()? {
+            if len == KeyStringN::<N>::CAPACITY {
+                return Err(de::Error::invalid_length(len + 1, &self))
+            }
+            buffer[len] = byte;
+            len += 1;
+        }
+        self.visit_bytes(&buffer[0..len])
+    }
+}
+
+/// Fails on strings that are longer than the capacity or are not utf8, instead of truncating them.
+impl<'de, const N: usize> Deserialize<'de> for KeyStringN<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(KeyStringVisitor)
+        } else {
+            deserializer.deserialize_bytes(KeyStringVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    #[test]
+    fn json_round_trip() {
+        let key = KeyStringN::<64>::from("users");
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(json, "\"users\"");
+        assert_eq!(serde_json::from_str::<KeyString>(&json).unwrap(), key);
+
+        let too_long = serde_json::from_str::<KeyStringN<4>>("\"users\"").unwrap_err();
+        assert!(too_long.to_string().contains("at most 3 bytes"));
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let key = KeyStringN::<64>::from("users");
+        let bytes = bincode::serialize(&key).unwrap();
+        assert_eq!(bytes.len(), 8 + 5);
+        assert_eq!(bincode::deserialize::<KeyString>(&bytes).unwrap(), key);
+
+        assert!(bincode::deserialize::<KeyStringN<4>>(&bytes).is_err());
+        let invalid = bincode::serialize(&vec![0xffu8, 0xfe]).unwrap();
+        assert!(bincode::deserialize::<KeyString>(&invalid).is_err());
+    }
+}
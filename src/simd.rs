@@ -0,0 +1,167 @@
+//! Wide comparisons of KeyString buffers.
+//! 
+//! Comparing two KeyStrings only needs the first byte where their buffers differ: if it is inside both strings
+//! it decides the order, otherwise one is a prefix of the other and the lengths decide.
+//! On x86_64 the search uses AVX2 when the cpu has it and SSE2 otherwise, elsewhere it compares 8 bytes at a time.
+//! Every path gives the same answer as comparing `as_str()`.
+//! 
+//! There is no search for the length, since it is stored in the last byte of the buffer.
+//! Equality stays the derived one, which compiles to a vectorized memcmp and is what lets KeyString constants be used as match patterns.
+
+use std::cmp::Ordering;
+
+use crate::KeyStringN;
+
+/// The index of the first byte where a and b differ, or None if they are equal. a and b must be the same length.
+#[inline]
+pub(crate) fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    debug_assert_eq!(a.len(), b.len());
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // Safe since the cpu was just checked for AVX2
+            unsafe { x86::first_difference_avx2(a, b) }
+        } else {
+            // Safe since SSE2 is part of x86_64
+            unsafe { x86::first_difference_sse2(a, b) }
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        first_difference_portable(a, b)
+    }
+}
+
+pub(crate) fn first_difference_portable(a: &[u8], b: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    while offset + 8 <= a.len() {
+        let x = u64::from_le_bytes(a[offset..offset + 8].try_into().unwrap());
+        let y = u64::from_le_bytes(b[offset..offset + 8].try_into().unwrap());
+        let difference = x ^ y;
+        if difference != 0 {
+            return Some(offset + difference.trailing_zeros() as usize / 8)
+        }
+        offset += 8;
+    }
+    a[offset..].iter().zip(&b[offset..]).position(|(x, y)| x != y).map(|index| offset + index)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::first_difference_portable;
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn first_difference_sse2(a: &[u8], b: &[u8]) -> Option<usize> {
+        let mut offset = 0;
+        while offset + 16 <= a.len() {
+            let x = _mm_loadu_si128(a.as_ptr().add(offset) as *const __m128i);
+            let y = _mm_loadu_si128(b.as_ptr().add(offset) as *const __m128i);
+            let equal = _mm_movemask_epi8(_mm_cmpeq_epi8(x, y)) as u32;
+            if equal != 0xFFFF {
+                return Some(offset + (!equal).trailing_zeros() as usize)
+            }
+            offset += 16;
+        }
+        first_difference_portable(&a[offset..], &b[offset..]).map(|index| offset + index)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn first_difference_avx2(a: &[u8], b: &[u8]) -> Option<usize> {
+        let mut offset = 0;
+        while offset + 32 <= a.len() {
+            let x = _mm256_loadu_si256(a.as_ptr().add(offset) as *const __m256i);
+            let y = _mm256_loadu_si256(b.as_ptr().add(offset) as *const __m256i);
+            let equal = _mm256_movemask_epi8(_mm256_cmpeq_epi8(x, y)) as u32;
+            if equal != u32::MAX {
+                return Some(offset + (!equal).trailing_zeros() as usize)
+            }
+            offset += 32;
+        }
+        first_difference_sse2(&a[offset..], &b[offset..]).map(|index| offset + index)
+    }
+}
+
+/// Turns the first difference between two buffers into the order of the strings in them.
+#[inline]
+pub(crate) fn order_from_difference<const N: usize>(a: &KeyStringN<N>, b: &KeyStringN<N>, difference: Option<usize>) -> Ordering {
+    match difference {
+        None => Ordering::Equal,
+        Some(index) if index < std::cmp::min(a.len(), b.len()) => a.inner[index].cmp(&b.inner[index]),
+        Some(_) => a.len().cmp(&b.len()),
+    }
+}
+
+impl<const N: usize> KeyStringN<N> {
+
+    /// Compares self against every key, writing `self.cmp(&keys[i])` into `output[i]`.
+    /// 
+    /// # Panics
+    /// Panics if keys and output are not the same length.
+    pub fn cmp_batch(&self, keys: &[Self], output: &mut [Ordering]) {
+        assert_eq!(keys.len(), output.len(), "keys and output must be the same length");
+        for (key, ordering) in keys.iter().zip(output.iter_mut()) {
+            *ordering = order_from_difference(self, key, first_difference(&self.inner, &key.inner));
+        }
+    }
+
+    /// The index of the first key that is equal to self.
+    pub fn find_in(&self, keys: &[Self]) -> Option<usize> {
+        keys.iter().position(|key| first_difference(&self.inner, &key.inner).is_none())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    fn keys() -> Vec<KeyString> {
+        let mut output = vec![KeyString::new()];
+        let mut state: u64 = 0x2545F4914F6CDD1D;
+        for length in 0..64 {
+            for _ in 0..8 {
+                let mut text = String::new();
+                for _ in 0..length {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    // Few distinct chars, including 0 and multi byte ones, so there are lots of shared prefixes
+                    text.push(['a', 'b', '\0', 'é', '\u{7f}'][(state % 5) as usize]);
+                }
+                output.push(KeyString::from(text.as_str()));
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn matches_scalar() {
+        let keys = keys();
+        for a in &keys {
+            for b in &keys {
+                let scalar = a.as_str().cmp(b.as_str());
+                assert_eq!(a.cmp(b), scalar);
+                assert_eq!(order_from_difference(a, b, first_difference_portable(a.raw(), b.raw())), scalar);
+                #[cfg(target_arch = "x86_64")]
+                assert_eq!(order_from_difference(a, b, unsafe { x86::first_difference_sse2(a.raw(), b.raw()) }), scalar);
+                assert_eq!(a == b, scalar == Ordering::Equal);
+            }
+        }
+    }
+
+    #[test]
+    fn batches() {
+        let keys = keys();
+        let probe = keys[100];
+        let mut output = vec![Ordering::Equal; keys.len()];
+        probe.cmp_batch(&keys, &mut output);
+        for (key, ordering) in keys.iter().zip(&output) {
+            assert_eq!(*ordering, probe.as_str().cmp(key.as_str()));
+        }
+        assert_eq!(probe.find_in(&keys), keys.iter().position(|key| *key == probe));
+        assert_eq!(KeyString::from("not there").find_in(&keys), None);
+    }
+}
@@ -0,0 +1,666 @@
+//! An adaptive radix tree over the bytes of KeyStrings.
+//! 
+//! Each node stores the bytes its keys have in common once (path compression), so keys with long shared
+//! prefixes like `tenant_42/table/column` cost little more than their distinct suffixes. A key is one node,
+//! which keeps suffixes of up to 22 bytes inline and has no child array until it gets children, so a leaf
+//! with a u32 value is a single 48 byte allocation on 64 bit platforms.
+//! Nodes grow and shrink between 4, 16, 48 and 256 children as in an ART, so sparse nodes stay small
+//! and dense ones are a single index. Keys are not stored whole, they are rebuilt from the path when iterating.
+//! 
+//! Iteration is in `impl Ord for KeyString` order: a node's own key comes before its children, and children go by byte.
+
+use crate::KeyStringN;
+
+struct Node<V> {
+    prefix: Prefix,
+    value: Option<V>,
+    children: Children<V>,
+}
+
+const INLINE_PREFIX: usize = 22;
+
+/// The compressed path of a node, stored inline when it is short, which it is for most leaves.
+enum Prefix {
+    Inline { len: u8, bytes: [u8; INLINE_PREFIX] },
+    Heap(Box<[u8]>),
+}
+
+impl From<&[u8]> for Prefix {
+    fn from(bytes: &[u8]) -> Self {
+        if bytes.len() <= INLINE_PREFIX {
+            let mut inline = [0; INLINE_PREFIX];
+            inline[0..bytes.len()].copy_from_slice(bytes);
+            Prefix::Inline { len: bytes.len() as u8, bytes: inline }
+        } else {
+            Prefix::Heap(bytes.into())
+        }
+    }
+}
+
+impl std::ops::Deref for Prefix {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Prefix::Inline { len, bytes } => &bytes[0..*len as usize],
+            Prefix::Heap(bytes) => bytes,
+        }
+    }
+}
+
+struct Small<V, const C: usize> {
+    len: usize,
+    keys: [u8; C],
+    children: [Option<Box<Node<V>>>; C],
+}
+
+struct Node48<V> {
+    /// 0 means no child, otherwise the slot of the child plus one
+    index: [u8; 256],
+    slots: [Option<Box<Node<V>>>; 48],
+    len: usize,
+}
+
+struct Node256<V> {
+    children: [Option<Box<Node<V>>>; 256],
+    len: usize,
+}
+
+/// Every size is boxed, so a node without children only pays for the tag and a pointer.
+enum Children<V> {
+    Leaf,
+    Node4(Box<Small<V, 4>>),
+    Node16(Box<Small<V, 16>>),
+    Node48(Box<Node48<V>>),
+    Node256(Box<Node256<V>>),
+}
+
+impl<V, const C: usize> Small<V, C> {
+
+    fn new() -> Self {
+        Small { len: 0, keys: [0; C], children: [const { None }; C] }
+    }
+
+    fn position(&self, byte: u8) -> Result<usize, usize> {
+        self.keys[0..self.len].binary_search(&byte)
+    }
+
+    fn insert(&mut self, byte: u8, child: Box<Node<V>>) {
+        let position = self.position(byte).unwrap_err();
+        for index in (position..self.len).rev() {
+            self.keys[index + 1] = self.keys[index];
+            self.children[index + 1] = self.children[index].take();
+        }
+        self.keys[position] = byte;
+        self.children[position] = Some(child);
+        self.len += 1;
+    }
+
+    fn remove(&mut self, byte: u8) -> Option<Box<Node<V>>> {
+        let position = self.position(byte).ok()?;
+        let child = self.children[position].take();
+        for index in position..self.len - 1 {
+            self.keys[index] = self.keys[index + 1];
+            self.children[index] = self.children[index + 1].take();
+        }
+        self.len -= 1;
+        child
+    }
+
+    fn drain(&mut self) -> impl Iterator<Item = (u8, Box<Node<V>>)> + '_ {
+        let len = std::mem::take(&mut self.len);
+        self.keys[0..len].iter().zip(self.children[0..len].iter_mut()).map(|(byte, child)| (*byte, child.take().unwrap()))
+    }
+
+}
+
+impl<V> Node48<V> {
+
+    fn new() -> Self {
+        Node48 { index: [0; 256], slots: [const { None }; 48], len: 0 }
+    }
+
+    fn insert(&mut self, byte: u8, child: Box<Node<V>>) {
+        let slot = self.slots.iter().position(Option::is_none).unwrap();
+        self.slots[slot] = Some(child);
+        self.index[byte as usize] = slot as u8 + 1;
+        self.len += 1;
+    }
+
+    fn remove(&mut self, byte: u8) -> Option<Box<Node<V>>> {
+        let slot = std::mem::take(&mut self.index[byte as usize]).checked_sub(1)?;
+        self.len -= 1;
+        self.slots[slot as usize].take()
+    }
+
+}
+
+impl<V> Children<V> {
+
+    fn len(&self) -> usize {
+        match self {
+            Children::Leaf => 0,
+            Children::Node4(node) => node.len,
+            Children::Node16(node) => node.len,
+            Children::Node48(node) => node.len,
+            Children::Node256(node) => node.len,
+        }
+    }
+
+    fn get(&self, byte: u8) -> Option<&Node<V>> {
+        match self {
+            Children::Leaf => None,
+            Children::Node4(node) => node.position(byte).ok().and_then(|i| node.children[i].as_deref()),
+            Children::Node16(node) => node.position(byte).ok().and_then(|i| node.children[i].as_deref()),
+            Children::Node48(node) => match node.index[byte as usize] {
+                0 => None,
+                slot => node.slots[slot as usize - 1].as_deref(),
+            },
+            Children::Node256(node) => node.children[byte as usize].as_deref(),
+        }
+    }
+
+    fn get_mut(&mut self, byte: u8) -> Option<&mut Node<V>> {
+        match self {
+            Children::Leaf => None,
+            Children::Node4(node) => node.position(byte).ok().and_then(|i| node.children[i].as_deref_mut()),
+            Children::Node16(node) => node.position(byte).ok().and_then(|i| node.children[i].as_deref_mut()),
+            Children::Node48(node) => match node.index[byte as usize] {
+                0 => None,
+                slot => node.slots[slot as usize - 1].as_deref_mut(),
+            },
+            Children::Node256(node) => node.children[byte as usize].as_deref_mut(),
+        }
+    }
+
+    /// The child with the smallest byte that is at least from.
+    fn next_from(&self, from: usize) -> Option<(u8, &Node<V>)> {
+        match self {
+            Children::Leaf => None,
+            Children::Node4(node) => {
+                let start = node.keys[0..node.len].partition_point(|byte| (*byte as usize) < from);
+                (start < node.len).then(|| (node.keys[start], node.children[start].as_deref().unwrap()))
+            },
+            Children::Node16(node) => {
+                let start = node.keys[0..node.len].partition_point(|byte| (*byte as usize) < from);
+                (start < node.len).then(|| (node.keys[start], node.children[start].as_deref().unwrap()))
+            },
+            Children::Node48(node) => (from..256)
+                .find(|byte| node.index[*byte] != 0)
+                .map(|byte| (byte as u8, node.slots[node.index[byte] as usize - 1].as_deref().unwrap())),
+            Children::Node256(node) => (from..256)
+                .find_map(|byte| node.children[byte].as_deref().map(|child| (byte as u8, child))),
+        }
+    }
+
+    /// Adds a child for a byte that has none, growing to the next node size if this one is full.
+    fn insert(&mut self, byte: u8, child: Box<Node<V>>) {
+        match self {
+            Children::Leaf => {
+                let mut node = Box::new(Small::new());
+                node.insert(byte, child);
+                *self = Children::Node4(node);
+            },
+            Children::Node4(node) if node.len == 4 => {
+                let mut grown = Box::new(Small::<V, 16>::new());
+                for (byte, child) in node.drain() {
+                    grown.insert(byte, child);
+                }
+                grown.insert(byte, child);
+                *self = Children::Node16(grown);
+            },
+            Children::Node16(node) if node.len == 16 => {
+                let mut grown = Box::new(Node48::new());
+                for (byte, child) in node.drain() {
+                    grown.insert(byte, child);
+                }
+                grown.insert(byte, child);
+                *self = Children::Node48(grown);
+            },
+            Children::Node48(node) if node.len == 48 => {
+                let mut grown = Box::new(Node256 { children: [const { None }; 256], len: 0 });
+                for (byte, slot) in node.index.iter().enumerate() {
+                    if *slot != 0 {
+                        grown.children[byte] = node.slots[*slot as usize - 1].take();
+                        grown.len += 1;
+                    }
+                }
+                grown.children[byte as usize] = Some(child);
+                grown.len += 1;
+                *self = Children::Node256(grown);
+            },
+            Children::Node4(node) => node.insert(byte, child),
+            Children::Node16(node) => node.insert(byte, child),
+            Children::Node48(node) => node.insert(byte, child),
+            Children::Node256(node) => {
+                node.children[byte as usize] = Some(child);
+                node.len += 1;
+            },
+        }
+    }
+
+    /// Removes the child for byte, shrinking to a smaller node size once there is room to spare.
+    fn remove(&mut self, byte: u8) -> Option<Box<Node<V>>> {
+        let removed = match self {
+            Children::Leaf => None,
+            Children::Node4(node) => node.remove(byte),
+            Children::Node16(node) => node.remove(byte),
+            Children::Node48(node) => node.remove(byte),
+            Children::Node256(node) => {
+                let removed = node.children[byte as usize].take();
+                if removed.is_some() {
+                    node.len -= 1;
+                }
+                removed
+            },
+        };
+        match self {
+            Children::Node4(node) if node.len == 0 => *self = Children::Leaf,
+            Children::Node16(node) if node.len <= 3 => {
+                let mut shrunk = Box::new(Small::<V, 4>::new());
+                for (byte, child) in node.drain() {
+                    shrunk.insert(byte, child);
+                }
+                *self = Children::Node4(shrunk);
+            },
+            Children::Node48(node) if node.len <= 12 => {
+                let mut shrunk = Box::new(Small::<V, 16>::new());
+                for byte in 0..256 {
+                    if let Some(child) = node.remove(byte as u8) {
+                        shrunk.insert(byte as u8, child);
+                    }
+                }
+                *self = Children::Node16(shrunk);
+            },
+            Children::Node256(node) if node.len <= 40 => {
+                let mut shrunk = Box::new(Node48::new());
+                for (byte, child) in node.children.iter_mut().enumerate() {
+                    if let Some(child) = child.take() {
+                        shrunk.insert(byte as u8, child);
+                    }
+                }
+                *self = Children::Node48(shrunk);
+            },
+            _ => (),
+        }
+        removed
+    }
+
+    /// Removes and returns the only child.
+    fn take_only(&mut self) -> (u8, Box<Node<V>>) {
+        debug_assert_eq!(self.len(), 1);
+        let byte = self.next_from(0).unwrap().0;
+        (byte, self.remove(byte).unwrap())
+    }
+
+}
+
+impl<V> Node<V> {
+
+    fn new(prefix: &[u8], value: Option<V>) -> Self {
+        Node { prefix: Prefix::from(prefix), value, children: Children::Leaf }
+    }
+
+    fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        let common = common_prefix_len(&self.prefix, key);
+        if common < self.prefix.len() {
+            // Split the prefix: this node keeps the common part and the old contents move to a child
+            let mut old = std::mem::replace(self, Node::new(&key[0..common], None));
+            let byte = old.prefix[common];
+            old.prefix = Prefix::from(&old.prefix[common + 1..]);
+            self.children.insert(byte, Box::new(old));
+        }
+        let rest = &key[common..];
+        match rest.split_first() {
+            None => self.value.replace(value),
+            Some((byte, rest)) => match self.children.get_mut(*byte) {
+                Some(child) => child.insert(rest, value),
+                None => {
+                    self.children.insert(*byte, Box::new(Node::new(rest, Some(value))));
+                    None
+                },
+            },
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let rest = key.strip_prefix(&*self.prefix)?;
+        let (byte, rest) = match rest.split_first() {
+            None => return self.value.take(),
+            Some(split) => split,
+        };
+        let child = self.children.get_mut(*byte)?;
+        let value = child.remove(rest)?;
+        if child.value.is_none() && child.children.len() == 0 {
+            self.children.remove(*byte);
+        } else {
+            child.compact();
+        }
+        Some(value)
+    }
+
+    /// Merges a node with no value and a single child into that child, to keep paths compressed.
+    fn compact(&mut self) {
+        if self.value.is_none() && self.children.len() == 1 {
+            let (byte, child) = self.children.take_only();
+            let child = *child;
+            let mut prefix = self.prefix.to_vec();
+            prefix.push(byte);
+            prefix.extend_from_slice(&child.prefix);
+            self.prefix = Prefix::from(prefix.as_slice());
+            self.value = child.value;
+            self.children = child.children;
+        }
+    }
+
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// An ordered map from `KeyStringN<N>` to V, stored as an adaptive radix tree. See the module documentation.
+pub struct KeyTrie<V, const N: usize = 64> {
+    root: Option<Box<Node<V>>>,
+    len: usize,
+}
+
+impl<V, const N: usize> Default for KeyTrie<V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V, const N: usize> KeyTrie<V, N> {
+
+    pub fn new() -> Self {
+        KeyTrie { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the old value if the key was already there.
+    pub fn insert(&mut self, key: KeyStringN<N>, value: V) -> Option<V> {
+        let old = match &mut self.root {
+            None => {
+                self.root = Some(Box::new(Node::new(key.as_bytes(), Some(value))));
+                None
+            },
+            Some(root) => root.insert(key.as_bytes(), value),
+        };
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn get(&self, key: &KeyStringN<N>) -> Option<&V> {
+        let mut node = self.root.as_deref()?;
+        let mut rest = key.as_bytes();
+        loop {
+            rest = rest.strip_prefix(&*node.prefix)?;
+            match rest.split_first() {
+                None => return node.value.as_ref(),
+                Some((byte, tail)) => {
+                    node = node.children.get(*byte)?;
+                    rest = tail;
+                },
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &KeyStringN<N>) -> Option<&mut V> {
+        let mut node = self.root.as_deref_mut()?;
+        let mut rest = key.as_bytes();
+        loop {
+            rest = rest.strip_prefix(&*node.prefix)?;
+            match rest.split_first() {
+                None => return node.value.as_mut(),
+                Some((byte, tail)) => {
+                    node = node.children.get_mut(*byte)?;
+                    rest = tail;
+                },
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &KeyStringN<N>) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: &KeyStringN<N>) -> Option<V> {
+        let root = self.root.as_mut()?;
+        let value = root.remove(key.as_bytes())?;
+        if root.value.is_none() && root.children.len() == 0 {
+            self.root = None;
+        } else {
+            root.compact();
+        }
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// The longest key in the trie that is a prefix of query, with its value.
+    pub fn longest_prefix_match(&self, query: &str) -> Option<(KeyStringN<N>, &V)> {
+        let mut node = self.root.as_deref()?;
+        let query = query.as_bytes();
+        let mut depth = 0;
+        let mut best = None;
+        loop {
+            if !query[depth..].starts_with(&node.prefix) {
+                break
+            }
+            depth += node.prefix.len();
+            if let Some(value) = &node.value {
+                best = Some((depth, value));
+            }
+            match query.get(depth).and_then(|byte| node.children.get(*byte)) {
+                Some(child) => {
+                    node = child;
+                    depth += 1;
+                },
+                None => break,
+            }
+        }
+        // Safe since every key in the trie came from a KeyStringN<N>, so it is utf8 and fits
+        best.map(|(depth, value)| (KeyStringN::from_bytes_unchecked(&query[0..depth]), value))
+    }
+
+    /// Every entry in key order.
+    pub fn iter(&self) -> Iter<'_, V, N> {
+        Iter::new(self.root.as_deref(), Vec::new())
+    }
+
+    /// The entries whose keys start with prefix, in key order.
+    pub fn prefix_iter(&self, prefix: &str) -> Iter<'_, V, N> {
+        let mut node = match self.root.as_deref() {
+            Some(node) => node,
+            None => return Iter::new(None, Vec::new()),
+        };
+        let mut rest = prefix.as_bytes();
+        let mut path = Vec::new();
+        loop {
+            let common = common_prefix_len(&node.prefix, rest);
+            if common == rest.len() {
+                // The prefix ends inside this node, so everything under it matches
+                return Iter::new(Some(node), path)
+            }
+            if common < node.prefix.len() {
+                return Iter::new(None, Vec::new())
+            }
+            path.extend_from_slice(&node.prefix);
+            let byte = rest[common];
+            path.push(byte);
+            rest = &rest[common + 1..];
+            node = match node.children.get(byte) {
+                Some(child) => child,
+                None => return Iter::new(None, Vec::new()),
+            };
+        }
+    }
+
+}
+
+impl<V, const N: usize> FromIterator<(KeyStringN<N>, V)> for KeyTrie<V, N> {
+    fn from_iter<I: IntoIterator<Item = (KeyStringN<N>, V)>>(iter: I) -> Self {
+        let mut trie = Self::new();
+        for (key, value) in iter {
+            trie.insert(key, value);
+        }
+        trie
+    }
+}
+
+struct Frame<'a, V> {
+    node: &'a Node<V>,
+    /// The length of the key before this node's prefix
+    depth: usize,
+    visited_value: bool,
+    next_byte: usize,
+}
+
+/// Iterates over the entries of a KeyTrie in key order, rebuilding each key from its path.
+pub struct Iter<'a, V, const N: usize> {
+    stack: Vec<Frame<'a, V>>,
+    key: Vec<u8>,
+}
+
+impl<'a, V, const N: usize> Iter<'a, V, N> {
+
+    fn new(start: Option<&'a Node<V>>, path: Vec<u8>) -> Self {
+        let depth = path.len();
+        Iter {
+            stack: start.into_iter().map(|node| Frame { node, depth, visited_value: false, next_byte: 0 }).collect(),
+            key: path,
+        }
+    }
+
+}
+
+impl<'a, V, const N: usize> Iterator for Iter<'a, V, N> {
+    type Item = (KeyStringN<N>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = self.stack.last_mut()?;
+            let node = frame.node;
+            let end = frame.depth + node.prefix.len();
+            if !frame.visited_value {
+                frame.visited_value = true;
+                self.key.truncate(frame.depth);
+                self.key.extend_from_slice(&node.prefix);
+                if let Some(value) = &node.value {
+                    // Safe since every key in the trie came from a KeyStringN<N>, so it is utf8 and fits
+                    return Some((KeyStringN::from_bytes_unchecked(&self.key), value))
+                }
+            }
+            match node.children.next_from(frame.next_byte) {
+                Some((byte, child)) => {
+                    frame.next_byte = byte as usize + 1;
+                    self.key.truncate(end);
+                    self.key.push(byte);
+                    self.stack.push(Frame { node: child, depth: end + 1, visited_value: false, next_byte: 0 });
+                },
+                None => {
+                    self.stack.pop();
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+    use std::collections::BTreeMap;
+
+    fn key(s: &str) -> KeyString {
+        KeyString::from(s)
+    }
+
+    #[test]
+    fn basic_operations() {
+        let mut trie: KeyTrie<usize> = KeyTrie::new();
+        for (i, name) in ["tenant_42/users/id", "tenant_42/users", "tenant_42/orders/id", "tenant_7", "", "t"].iter().enumerate() {
+            assert_eq!(trie.insert(key(name), i), None);
+        }
+        assert_eq!(trie.insert(key("t"), 10), Some(5));
+        assert_eq!(trie.len(), 6);
+        assert_eq!(trie.get(&key("tenant_42/users")), Some(&1));
+        assert_eq!(trie.get(&key("tenant_42/user")), None);
+        assert_eq!(trie.get(&key("")), Some(&4));
+        *trie.get_mut(&key("tenant_7")).unwrap() += 100;
+        assert_eq!(trie.get(&key("tenant_7")), Some(&103));
+
+        let (matched, value) = trie.longest_prefix_match("tenant_42/users/name").unwrap();
+        assert_eq!((matched.as_str(), *value), ("tenant_42/users", 1));
+        assert_eq!(trie.longest_prefix_match("x").unwrap().0.as_str(), "");
+
+        let prefixed: Vec<String> = trie.prefix_iter("tenant_4").map(|(k, _)| k.as_str().to_string()).collect();
+        assert_eq!(prefixed, vec!["tenant_42/orders/id", "tenant_42/users", "tenant_42/users/id"]);
+        assert_eq!(trie.prefix_iter("tenant_42/users/").count(), 1);
+        assert_eq!(trie.prefix_iter("nothing").count(), 0);
+
+        assert_eq!(trie.remove(&key("tenant_42/users")), Some(1));
+        assert_eq!(trie.remove(&key("tenant_42/users")), None);
+        assert_eq!(trie.get(&key("tenant_42/users/id")), Some(&0));
+        assert_eq!(trie.len(), 5);
+    }
+
+    #[test]
+    fn matches_btreemap() {
+        let mut trie: KeyTrie<u32> = KeyTrie::new();
+        let mut model: BTreeMap<KeyString, u32> = BTreeMap::new();
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        // Wide fan out at one position so nodes grow all the way to 256 children and shrink back
+        let alphabet: Vec<char> = (0u32..0x80).chain([0xE9, 0x3B1, 0x4E2D, 0x1F600, 0x7FF, 0xFFFD]).filter_map(char::from_u32).collect();
+        for round in 0..6000u32 {
+            let mut text = String::from("p/");
+            for _ in 0..(random() % 4) {
+                text.push(alphabet[(random() % alphabet.len() as u64) as usize]);
+            }
+            let k = KeyString::from(text.as_str());
+            if round < 4000 || random() % 3 == 0 {
+                assert_eq!(trie.insert(k, round), model.insert(k, round));
+            } else {
+                assert_eq!(trie.remove(&k), model.remove(&k));
+            }
+        }
+        assert_eq!(trie.len(), model.len());
+        assert!(trie.iter().map(|(k, v)| (k, *v)).eq(model.iter().map(|(k, v)| (*k, *v))));
+
+        let keys: Vec<KeyString> = model.keys().copied().collect();
+        for k in keys {
+            assert_eq!(trie.remove(&k), model.remove(&k));
+        }
+        assert!(trie.is_empty());
+        assert!(trie.root.is_none());
+    }
+
+    #[test]
+    fn leaf_size() {
+        // Pinned, since the module documentation promises it
+        assert!(std::mem::size_of::<Node<u32>>() <= 48);
+        let mut trie: KeyTrie<u32> = KeyTrie::new();
+        trie.insert(key("tenant_42/users/id"), 1);
+        trie.insert(key("tenant_42/users/name_of_a_very_long_column"), 2);
+        let root = trie.root.as_deref().unwrap();
+        assert!(matches!(root.prefix, Prefix::Inline { .. }));
+        let long = root.children.get(b'n').unwrap();
+        assert!(matches!(long.prefix, Prefix::Heap(_)) && matches!(long.children, Children::Leaf));
+        assert_eq!(&*long.prefix, b"ame_of_a_very_long_column");
+    }
+}
@@ -0,0 +1,378 @@
+//! Order preserving encoding of typed tuples, for compound index keys like `(table, id, score)`.
+//! 
+//! `pack` turns a tuple into bytes whose lexicographic order is the order of the tuples, element by element.
+//! Integers have their sign bit flipped and are written big endian, floats are transformed so that they
+//! sort like `total_cmp` (so -0.0 sorts before 0.0 and NaNs sort at the ends), and strings are terminated
+//! by a 0 byte with 0 bytes inside them escaped as 0x00 0xFF, so a string sorts before any longer string it is a prefix of.
+//! Each element starts with a type code, so decoding checks that the types match.
+//! 
+//! `pack_key` additionally spreads those bytes over 7 bits per byte, which keeps the order but makes the
+//! result valid utf8, so it fits in a `KeyString` and sorts correctly under `impl Ord for KeyString`.
+//! `prefix_range_key` gives the range of keys that start with a given tuple, for range scans.
+
+use crate::{Error, KeyStringN};
+
+const BYTES: u8 = 0x01;
+const STRING: u8 = 0x02;
+const I8: u8 = 0x11;
+const I16: u8 = 0x12;
+const I32: u8 = 0x13;
+const I64: u8 = 0x14;
+const U8: u8 = 0x18;
+const U16: u8 = 0x19;
+const U32: u8 = 0x1A;
+const U64: u8 = 0x1B;
+const F32: u8 = 0x20;
+const F64: u8 = 0x21;
+const FALSE: u8 = 0x26;
+const TRUE: u8 = 0x27;
+
+/// A value that can be an element of a packed tuple.
+pub trait TupleElement: Sized {
+    /// Appends the type code and the encoded value to output.
+    fn encode(&self, output: &mut Vec<u8>);
+
+    /// Reads one element from the start of input and advances input past it.
+    fn decode(input: &mut &[u8]) -> Result<Self, Error>;
+}
+
+/// A tuple of TupleElements. Implemented for tuples of 1 to 8 elements.
+pub trait Tuple: Sized {
+    fn encode(&self, output: &mut Vec<u8>);
+
+    fn decode(input: &mut &[u8]) -> Result<Self, Error>;
+}
+
+fn take<'a>(input: &mut &'a [u8], count: usize) -> Result<&'a [u8], Error> {
+    if input.len() < count {
+        return Err(Error::MalformedBuffer)
+    }
+    let (taken, rest) = input.split_at(count);
+    *input = rest;
+    Ok(taken)
+}
+
+fn expect_code(input: &mut &[u8], code: u8) -> Result<(), Error> {
+    match take(input, 1)? {
+        [byte] if *byte == code => Ok(()),
+        _ => Err(Error::MalformedBuffer),
+    }
+}
+
+fn encode_escaped(code: u8, bytes: &[u8], output: &mut Vec<u8>) {
+    output.push(code);
+    for byte in bytes {
+        output.push(*byte);
+        if *byte == 0 {
+            output.push(0xFF);
+        }
+    }
+    output.push(0);
+}
+
+fn decode_escaped(input: &mut &[u8], code: u8) -> Result<Vec<u8>, Error> {
+    expect_code(input, code)?;
+    let mut output = Vec::new();
+    loop {
+        match take(input, 1)? {
+            [0] => {
+                if input.first() == Some(&0xFF) {
+                    *input = &input[1..];
+                    output.push(0);
+                } else {
+                    return Ok(output)
+                }
+            },
+            [byte] => output.push(*byte),
+            _ => unreachable!(),
+        }
+    }
+}
+
+macro_rules! signed_elements {
+    ($($t:ty => $code:expr),*) => {
+        $(
+            impl TupleElement for $t {
+                fn encode(&self, output: &mut Vec<u8>) {
+                    output.push($code);
+                    let mut bytes = self.to_be_bytes();
+                    bytes[0] ^= 0x80;
+                    output.extend_from_slice(&bytes);
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+                    expect_code(input, $code)?;
+                    let mut bytes: [u8; std::mem::size_of::<$t>()] = take(input, std::mem::size_of::<$t>())?.try_into().unwrap();
+                    bytes[0] ^= 0x80;
+                    Ok(<$t>::from_be_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! unsigned_elements {
+    ($($t:ty => $code:expr),*) => {
+        $(
+            impl TupleElement for $t {
+                fn encode(&self, output: &mut Vec<u8>) {
+                    output.push($code);
+                    output.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+                    expect_code(input, $code)?;
+                    let bytes = take(input, std::mem::size_of::<$t>())?.try_into().unwrap();
+                    Ok(<$t>::from_be_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! float_elements {
+    ($($t:ty, $bits:ty => $code:expr),*) => {
+        $(
+            impl TupleElement for $t {
+                fn encode(&self, output: &mut Vec<u8>) {
+                    output.push($code);
+                    let bits = self.to_bits();
+                    let sign = 1 << (<$bits>::BITS - 1);
+                    // Negative numbers have all bits flipped so larger magnitudes sort first, positive ones just the sign bit
+                    let bits = if bits & sign != 0 { !bits } else { bits ^ sign };
+                    output.extend_from_slice(&bits.to_be_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+                    expect_code(input, $code)?;
+                    let bits = <$bits>::from_be_bytes(take(input, std::mem::size_of::<$bits>())?.try_into().unwrap());
+                    let sign = 1 << (<$bits>::BITS - 1);
+                    let bits = if bits & sign != 0 { bits ^ sign } else { !bits };
+                    Ok(<$t>::from_bits(bits))
+                }
+            }
+        )*
+    };
+}
+
+signed_elements!(i8 => I8, i16 => I16, i32 => I32, i64 => I64);
+unsigned_elements!(u8 => U8, u16 => U16, u32 => U32, u64 => U64);
+float_elements!(f32, u32 => F32, f64, u64 => F64);
+
+impl TupleElement for bool {
+    fn encode(&self, output: &mut Vec<u8>) {
+        output.push(if *self { TRUE } else { FALSE });
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        match take(input, 1)? {
+            [FALSE] => Ok(false),
+            [TRUE] => Ok(true),
+            _ => Err(Error::MalformedBuffer),
+        }
+    }
+}
+
+impl TupleElement for String {
+    fn encode(&self, output: &mut Vec<u8>) {
+        encode_escaped(STRING, self.as_bytes(), output);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        let bytes = decode_escaped(input, STRING)?;
+        String::from_utf8(bytes).map_err(|e| Error::InvalidUtf8(e.utf8_error()))
+    }
+}
+
+/// Encoded like a String, so the two can be used interchangeably.
+impl<const N: usize> TupleElement for KeyStringN<N> {
+    fn encode(&self, output: &mut Vec<u8>) {
+        encode_escaped(STRING, self.as_bytes(), output);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        let bytes = decode_escaped(input, STRING)?;
+        KeyStringN::from_utf8(&bytes)
+    }
+}
+
+impl TupleElement for Vec<u8> {
+    fn encode(&self, output: &mut Vec<u8>) {
+        encode_escaped(BYTES, self, output);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+        decode_escaped(input, BYTES)
+    }
+}
+
+macro_rules! tuples {
+    ($(($($name:ident),+)),*) => {
+        $(
+            impl<$($name: TupleElement),+> Tuple for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn encode(&self, output: &mut Vec<u8>) {
+                    let ($($name,)+) = self;
+                    $($name.encode(output);)+
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+                    Ok(($($name::decode(input)?,)+))
+                }
+            }
+        )*
+    };
+}
+
+tuples!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H)
+);
+
+/// Encodes a tuple into bytes that sort in the same order as the tuples.
+pub fn pack<T: Tuple>(tuple: &T) -> Vec<u8> {
+    let mut output = Vec::new();
+    tuple.encode(&mut output);
+    output
+}
+
+/// Decodes bytes made by `pack`. Fails if the types do not match or there are bytes left over.
+pub fn unpack<T: Tuple>(bytes: &[u8]) -> Result<T, Error> {
+    let mut input = bytes;
+    let output = T::decode(&mut input)?;
+    if !input.is_empty() {
+        return Err(Error::MalformedBuffer)
+    }
+    Ok(output)
+}
+
+/// Spreads bytes over the low 7 bits of each output byte, most significant bits first, padding the last one with 0 bits.
+/// The output is ascii, and comparing outputs gives the same order as comparing inputs.
+fn spread_7bit(bytes: &[u8], output: &mut Vec<u8>) {
+    let mut accumulator: u16 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        accumulator = (accumulator << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 7 {
+            bits -= 7;
+            output.push(((accumulator >> bits) & 0x7F) as u8);
+        }
+    }
+    if bits > 0 {
+        output.push(((accumulator << (7 - bits)) & 0x7F) as u8);
+    }
+}
+
+fn gather_7bit(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::with_capacity(bytes.len() * 7 / 8);
+    let mut accumulator: u16 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        if *byte > 0x7F {
+            return Err(Error::MalformedBuffer)
+        }
+        accumulator = (accumulator << 7) | *byte as u16;
+        bits += 7;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((accumulator >> bits) as u8);
+        }
+    }
+    Ok(output)
+}
+
+fn key_from_packed<const N: usize>(packed: &[u8]) -> Result<KeyStringN<N>, Error> {
+    let mut spread = Vec::with_capacity(packed.len() * 8 / 7 + 1);
+    spread_7bit(packed, &mut spread);
+    if spread.len() > KeyStringN::<N>::CAPACITY {
+        return Err(Error::CapacityExceeded { attempted: spread.len(), capacity: KeyStringN::<N>::CAPACITY })
+    }
+    // Safe since spread_7bit only produces ascii
+    Ok(unsafe { KeyStringN::from_utf8_unchecked(&spread) })
+}
+
+/// Encodes a tuple into a KeyString that sorts in the same order as the tuples.
+/// Fails if the encoding does not fit. Every 7 bytes of `pack` output take 8 bytes of the KeyString.
+pub fn pack_key<T: Tuple, const N: usize>(tuple: &T) -> Result<KeyStringN<N>, Error> {
+    key_from_packed(&pack(tuple))
+}
+
+/// Decodes a KeyString made by `pack_key`.
+pub fn unpack_key<T: Tuple, const N: usize>(key: &KeyStringN<N>) -> Result<T, Error> {
+    unpack(&gather_7bit(key.as_bytes())?)
+}
+
+/// The range of keys made by `pack_key` whose tuple starts with the elements of prefix.
+/// Every such key is `>= start` and `< end`, and no other key is.
+pub fn prefix_range_key<T: Tuple, const N: usize>(prefix: &T) -> Result<(KeyStringN<N>, KeyStringN<N>), Error> {
+    let mut packed = pack(prefix);
+    let start = key_from_packed(&packed)?;
+    // Every element starts with a type code below 0xFF, so this sorts after every key that continues the prefix
+    packed.push(0xFF);
+    let end = key_from_packed(&packed)?;
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyString;
+
+    #[test]
+    fn round_trips() {
+        let tuple = (String::from("us\0ers"), -5i64, 2.5f64, true, vec![0u8, 255], KeyStringN::<16>::from("x"));
+        let packed = pack(&tuple);
+        assert_eq!(unpack::<(String, i64, f64, bool, Vec<u8>, KeyStringN<16>)>(&packed).unwrap(), tuple);
+        assert!(unpack::<(String, u64, f64, bool, Vec<u8>, KeyStringN<16>)>(&packed).is_err());
+        assert!(unpack::<(String, i64)>(&packed).is_err());
+
+        let key: KeyString = pack_key(&(7u32, -1i8, f32::MIN)).unwrap();
+        assert_eq!(unpack_key::<(u32, i8, f32), 64>(&key).unwrap(), (7, -1, f32::MIN));
+        assert!(pack_key::<_, 8>(&(1i64, 2i64)).is_err());
+    }
+
+    #[test]
+    fn order_is_preserved() {
+        let mut tuples = vec![];
+        for table in ["a", "a\0", "ab", "b", ""] {
+            for id in [i64::MIN, -300, -1, 0, 1, 255, 256, i64::MAX] {
+                for score in [f64::NEG_INFINITY, -1e10, -0.5, -0.0, 0.0, 1e-300, 3.0, f64::INFINITY] {
+                    tuples.push((String::from(table), id, score));
+                }
+            }
+        }
+        let mut keys: Vec<(KeyString, (String, i64, f64))> = tuples.iter().map(|t| (pack_key(t).unwrap(), t.clone())).collect();
+        keys.sort_by_key(|a| a.0);
+        let from_keys: Vec<_> = keys.into_iter().map(|(_, t)| t).collect();
+
+        let mut expected = tuples.clone();
+        expected.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.total_cmp(&b.2)));
+        assert_eq!(from_keys.len(), expected.len());
+        for (a, b) in from_keys.iter().zip(&expected) {
+            assert_eq!(a.0, b.0);
+            assert_eq!(a.1, b.1);
+            assert_eq!(a.2.to_bits(), b.2.to_bits());
+        }
+    }
+
+    #[test]
+    fn prefix_ranges() {
+        let mut keys: Vec<KeyString> = vec![];
+        for table in ["users", "users2", "user", "orders"] {
+            for id in [-2i64, 0, 9] {
+                keys.push(pack_key(&(String::from(table), id)).unwrap());
+            }
+        }
+        keys.sort();
+        let (start, end) = prefix_range_key::<_, 64>(&(String::from("users"),)).unwrap();
+        let in_range: Vec<(String, i64)> = keys.iter().filter(|k| **k >= start && **k < end).map(|k| unpack_key(k).unwrap()).collect();
+        assert_eq!(in_range, vec![(String::from("users"), -2), (String::from("users"), 0), (String::from("users"), 9)]);
+    }
+}